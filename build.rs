@@ -0,0 +1,5 @@
+fn main() {
+    // Exposes `Py_LIMITED_API` and `Py_3_x`, since the buffer protocol is only part of the
+    // limited API since python 3.11.
+    pyo3_build_config::use_pyo3_cfgs();
+}
@@ -10,6 +10,7 @@ class PyMftAttribute:
     type_name: Any
     @classmethod
     def __init__(cls, *args, **kwargs) -> None: ...
+    def raw_bytes(self, *args, **kwargs) -> Any: ...
 
 class PyMftAttributeOther:
     data: Any
@@ -80,6 +81,18 @@ class PyMftAttributesIter:
     def __iter__(self) -> Any: ...
     def __next__(self) -> Any: ...
 
+class PyMftCarvedEntriesIterator:
+    @classmethod
+    def __init__(cls, *args, **kwargs) -> None: ...
+    def __iter__(self) -> Any: ...
+    def __next__(self) -> Any: ...
+
+class PyMftEntriesBatchIterator:
+    @classmethod
+    def __init__(cls, *args, **kwargs) -> None: ...
+    def __iter__(self) -> Any: ...
+    def __next__(self) -> Any: ...
+
 class PyMftEntriesIterator:
     @classmethod
     def __init__(cls, *args, **kwargs) -> None: ...
@@ -89,24 +102,71 @@ class PyMftEntriesIterator:
 class PyMftEntry:
     base_entry_id: Any
     base_entry_sequence: Any
+    empty: Any
     entry_id: Any
     file_size: Any
+    fixup_errors: Any
     flags: Any
     full_path: Any
+    full_paths: Any
     hard_link_count: Any
+    offset: Any
+    path_error: Any
+    path_status: Any
     sequence: Any
+    slack: Any
     total_entry_size: Any
     used_entry_size: Any
+    valid: Any
     @classmethod
     def __init__(cls, *args, **kwargs) -> None: ...
     def attributes(self, *args, **kwargs) -> Any: ...
+    def raw_bytes(self, *args, **kwargs) -> Any: ...
+    def slack_attributes(self, *args, **kwargs) -> Any: ...
+    def timestamp_anomalies(self, *args, **kwargs) -> Any: ...
+
+class PyMftEntryPath:
+    namespace: Any
+    parent_entry_id: Any
+    parent_entry_sequence: Any
+    path: Any
+    status: Any
+    @classmethod
+    def __init__(cls, *args, **kwargs) -> None: ...
+
+class PyMftFixupError:
+    expected: Any
+    found: Any
+    stride: Any
+    @classmethod
+    def __init__(cls, *args, **kwargs) -> None: ...
 
 class PyMftParser:
     @classmethod
     def __init__(cls, *args, **kwargs) -> None: ...
     def entries(self, *args, **kwargs) -> Any: ...
+    def entries_batched(self, *args, **kwargs) -> Any: ...
+    def entries_bodyfile(self, *args, **kwargs) -> Any: ...
     def entries_csv(self, *args, **kwargs) -> Any: ...
+    def entries_dict(self, *args, **kwargs) -> Any: ...
     def entries_json(self, *args, **kwargs) -> Any: ...
+    def entries_timeline(self, *args, **kwargs) -> Any: ...
     def number_of_entries(self, *args, **kwargs) -> Any: ...
+    def to_arrow(self, *args, **kwargs) -> Any: ...
+    def to_csv(self, *args, **kwargs) -> Any: ...
+    def to_jsonl(self, *args, **kwargs) -> Any: ...
+    def to_parquet(self, *args, **kwargs) -> Any: ...
     def __iter__(self) -> Any: ...
     def __next__(self) -> Any: ...
+
+class PyMftTimestampAnomaly:
+    field: Any
+    file_name: Any
+    kind: Any
+    reference_timestamp: Any
+    timestamp: Any
+    @classmethod
+    def __init__(cls, *args, **kwargs) -> None: ...
+
+def carve_entries(path_or_file_like: Any, alignment: int = ...) -> PyMftCarvedEntriesIterator: ...
+def parse_entry(data: bytes, entry_id: int = ...) -> PyMftEntry: ...
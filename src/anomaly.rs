@@ -0,0 +1,146 @@
+use crate::utils::date_to_pyobject;
+
+use mft_rs::attribute::MftAttributeType;
+use mft_rs::{FileNameAttr, MftEntry, StandardInfoAttr, Timestamp};
+use pyo3::prelude::*;
+
+use std::fmt;
+
+/// The kinds of timestamp anomalies that are looked for, all of which are common side effects
+/// of timestomping (setting the timestamps of a file through `SetFileTime` and the like).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnomalyKind {
+    /// $STANDARD_INFORMATION created is earlier than $FILE_NAME created.
+    /// $FILE_NAME timestamps can't be set from user mode, so are set when the file is created.
+    CreatedBeforeFileName,
+    /// A $STANDARD_INFORMATION timestamp has no sub-second part,
+    /// which is very unlikely for a timestamp set by the file system.
+    ZeroSubsecond,
+    /// $STANDARD_INFORMATION created or modified is later than its own mft_modified,
+    /// even though changing them updates mft_modified.
+    AfterMftModified,
+}
+
+impl fmt::Display for AnomalyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AnomalyKind::CreatedBeforeFileName => "created-before-file-name",
+            AnomalyKind::ZeroSubsecond => "zero-subsecond",
+            AnomalyKind::AfterMftModified => "after-mft-modified",
+        })
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone)]
+/// A $STANDARD_INFORMATION timestamp which looks like it was tampered with.
+pub struct PyMftTimestampAnomaly {
+    /// One of `created-before-file-name`, `zero-subsecond` or `after-mft-modified`.
+    #[pyo3(get)]
+    pub kind: String,
+    /// The $STANDARD_INFORMATION timestamp (`created`, `modified`, `mft_modified` or `accessed`).
+    #[pyo3(get)]
+    pub field: String,
+    value: Timestamp,
+    /// The timestamp `value` was compared to, if any.
+    reference: Option<Timestamp>,
+    /// The name of the $FILE_NAME attribute `value` was compared to, if any.
+    #[pyo3(get)]
+    pub file_name: Option<String>,
+}
+
+#[pymethods]
+impl PyMftTimestampAnomaly {
+    #[getter]
+    pub fn timestamp(&self) -> PyResult<Py<PyAny>> {
+        date_to_pyobject(&self.value)
+    }
+
+    #[getter]
+    pub fn reference_timestamp(&self) -> PyResult<Option<Py<PyAny>>> {
+        self.reference.as_ref().map(date_to_pyobject).transpose()
+    }
+}
+
+impl PyMftTimestampAnomaly {
+    fn new(kind: AnomalyKind, field: &str, value: Timestamp) -> Self {
+        PyMftTimestampAnomaly {
+            kind: kind.to_string(),
+            field: field.to_string(),
+            value,
+            reference: None,
+            file_name: None,
+        }
+    }
+}
+
+/// Compares the $STANDARD_INFORMATION timestamps of an entry to each other,
+/// and to those of its $FILE_NAME attributes.
+pub fn timestamp_anomalies(entry: &MftEntry) -> Vec<PyMftTimestampAnomaly> {
+    let mut standard_info: Option<StandardInfoAttr> = None;
+    let mut file_names: Vec<FileNameAttr> = vec![];
+
+    for attribute in entry
+        .iter_attributes_matching(Some(vec![
+            MftAttributeType::StandardInformation,
+            MftAttributeType::FileName,
+        ]))
+        .filter_map(Result::ok)
+    {
+        match attribute.header.type_code {
+            MftAttributeType::StandardInformation => {
+                standard_info = standard_info.or(attribute.data.into_standard_info())
+            }
+            _ => file_names.extend(attribute.data.into_file_name()),
+        }
+    }
+
+    let standard_info = match standard_info {
+        Some(standard_info) => standard_info,
+        None => return vec![],
+    };
+
+    let mut anomalies = vec![];
+
+    for file_name in &file_names {
+        if standard_info.created < file_name.created {
+            anomalies.push(PyMftTimestampAnomaly {
+                reference: Some(file_name.created),
+                file_name: Some(file_name.name.clone()),
+                ..PyMftTimestampAnomaly::new(
+                    AnomalyKind::CreatedBeforeFileName,
+                    "created",
+                    standard_info.created,
+                )
+            });
+        }
+    }
+
+    let fields = [
+        ("created", standard_info.created),
+        ("modified", standard_info.modified),
+        ("mft_modified", standard_info.mft_modified),
+        ("accessed", standard_info.accessed),
+    ];
+
+    for (field, value) in fields {
+        if value.subsec_nanosecond() == 0 {
+            anomalies.push(PyMftTimestampAnomaly::new(
+                AnomalyKind::ZeroSubsecond,
+                field,
+                value,
+            ));
+        }
+    }
+
+    for (field, value) in &fields[..2] {
+        if *value > standard_info.mft_modified {
+            anomalies.push(PyMftTimestampAnomaly {
+                reference: Some(standard_info.mft_modified),
+                ..PyMftTimestampAnomaly::new(AnomalyKind::AfterMftModified, field, *value)
+            });
+        }
+    }
+
+    anomalies
+}
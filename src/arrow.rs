@@ -0,0 +1,297 @@
+use crate::output::FlatMftEntryPaths;
+use crate::PyMftEntriesIterator;
+
+use arrow_array::builder::{
+    BooleanBuilder, StringBuilder, TimestampMicrosecondBuilder, UInt16Builder, UInt32Builder,
+    UInt64Builder,
+};
+use arrow_array::ffi_stream::FFI_ArrowArrayStream;
+use arrow_array::{ArrayRef, RecordBatch, RecordBatchReader};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use log::warn;
+use mft_rs::csv::FlatMftEntryWithName;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyCapsule;
+
+use std::ffi::CString;
+use std::io::Write;
+use std::sync::Arc;
+
+/// The schema of the arrow output, the same columns as the CSV output, with their actual types.
+/// Timestamps are in microseconds (the precision they are parsed with), in UTC.
+pub fn flat_schema() -> SchemaRef {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+
+    Arc::new(Schema::new(vec![
+        Field::new("Signature", DataType::Utf8, false),
+        Field::new("EntryId", DataType::UInt64, false),
+        Field::new("Sequence", DataType::UInt16, false),
+        Field::new("BaseEntryId", DataType::UInt64, false),
+        Field::new("BaseEntrySequence", DataType::UInt16, false),
+        Field::new("HardLinkCount", DataType::UInt16, false),
+        Field::new("Flags", DataType::Utf8, false),
+        Field::new("UsedEntrySize", DataType::UInt32, false),
+        Field::new("TotalEntrySize", DataType::UInt32, false),
+        Field::new("FileSize", DataType::UInt64, false),
+        Field::new("IsADirectory", DataType::Boolean, false),
+        Field::new("IsDeleted", DataType::Boolean, false),
+        Field::new("HasAlternateDataStreams", DataType::Boolean, false),
+        Field::new("StandardInfoFlags", DataType::Utf8, true),
+        Field::new("StandardInfoLastModified", timestamp.clone(), true),
+        Field::new("StandardInfoLastAccess", timestamp.clone(), true),
+        Field::new("StandardInfoCreated", timestamp.clone(), true),
+        Field::new("FileNameFlags", DataType::Utf8, true),
+        Field::new("FileNameLastModified", timestamp.clone(), true),
+        Field::new("FileNameLastAccess", timestamp.clone(), true),
+        Field::new("FileNameCreated", timestamp, true),
+        Field::new("FullPath", DataType::Utf8, false),
+        Field::new("PathStatus", DataType::Utf8, false),
+        Field::new("PathError", DataType::Utf8, false),
+        Field::new("FullPaths", DataType::Utf8, false),
+        Field::new("FullPathNamespaces", DataType::Utf8, false),
+    ]))
+}
+
+/// Builds a record batch of flat entries, a column at a time.
+#[derive(Default)]
+struct FlatBatchBuilder {
+    signature: StringBuilder,
+    entry_id: UInt64Builder,
+    sequence: UInt16Builder,
+    base_entry_id: UInt64Builder,
+    base_entry_sequence: UInt16Builder,
+    hard_link_count: UInt16Builder,
+    flags: StringBuilder,
+    used_entry_size: UInt32Builder,
+    total_entry_size: UInt32Builder,
+    file_size: UInt64Builder,
+    is_a_directory: BooleanBuilder,
+    is_deleted: BooleanBuilder,
+    has_alternate_data_streams: BooleanBuilder,
+    standard_info_flags: StringBuilder,
+    standard_info_last_modified: TimestampMicrosecondBuilder,
+    standard_info_last_access: TimestampMicrosecondBuilder,
+    standard_info_created: TimestampMicrosecondBuilder,
+    file_name_flags: StringBuilder,
+    file_name_last_modified: TimestampMicrosecondBuilder,
+    file_name_last_access: TimestampMicrosecondBuilder,
+    file_name_created: TimestampMicrosecondBuilder,
+    full_path: StringBuilder,
+    path_status: StringBuilder,
+    path_error: StringBuilder,
+    full_paths: StringBuilder,
+    full_path_namespaces: StringBuilder,
+}
+
+impl FlatBatchBuilder {
+    fn append(&mut self, entry: &FlatMftEntryWithName, paths: &FlatMftEntryPaths) {
+        self.signature.append_value(&entry.signature);
+        self.entry_id.append_value(entry.entry_id);
+        self.sequence.append_value(entry.sequence);
+        self.base_entry_id.append_value(entry.base_entry_id);
+        self.base_entry_sequence
+            .append_value(entry.base_entry_sequence);
+        self.hard_link_count.append_value(entry.hard_link_count);
+        // Flags are formatted the same way they are serialized.
+        self.flags.append_value(format!("{:?}", entry.flags));
+        self.used_entry_size.append_value(entry.used_entry_size);
+        self.total_entry_size.append_value(entry.total_entry_size);
+        self.file_size.append_value(entry.file_size);
+        self.is_a_directory.append_value(entry.is_a_directory);
+        self.is_deleted.append_value(entry.is_deleted);
+        self.has_alternate_data_streams
+            .append_value(entry.has_alternate_data_streams);
+        self.standard_info_flags.append_option(
+            entry
+                .standard_info_flags
+                .as_ref()
+                .map(|flags| format!("{flags:?}")),
+        );
+        self.standard_info_last_modified.append_option(
+            entry
+                .standard_info_last_modified
+                .map(|t| t.as_microsecond()),
+        );
+        self.standard_info_last_access
+            .append_option(entry.standard_info_last_access.map(|t| t.as_microsecond()));
+        self.standard_info_created
+            .append_option(entry.standard_info_created.map(|t| t.as_microsecond()));
+        self.file_name_flags.append_option(
+            entry
+                .file_name_flags
+                .as_ref()
+                .map(|flags| format!("{flags:?}")),
+        );
+        self.file_name_last_modified
+            .append_option(entry.file_name_last_modified.map(|t| t.as_microsecond()));
+        self.file_name_last_access
+            .append_option(entry.file_name_last_access.map(|t| t.as_microsecond()));
+        self.file_name_created
+            .append_option(entry.file_name_created.map(|t| t.as_microsecond()));
+        self.full_path
+            .append_value(entry.full_path.to_string_lossy());
+        self.path_status.append_value(&paths.path_status);
+        self.path_error.append_value(&paths.path_error);
+        self.full_paths.append_value(&paths.full_paths);
+        self.full_path_namespaces
+            .append_value(&paths.full_path_namespaces);
+    }
+
+    fn finish(mut self, schema: SchemaRef) -> Result<RecordBatch, ArrowError> {
+        let timestamps = |builder: &mut TimestampMicrosecondBuilder| -> ArrayRef {
+            Arc::new(builder.finish().with_timezone("UTC"))
+        };
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.signature.finish()),
+            Arc::new(self.entry_id.finish()),
+            Arc::new(self.sequence.finish()),
+            Arc::new(self.base_entry_id.finish()),
+            Arc::new(self.base_entry_sequence.finish()),
+            Arc::new(self.hard_link_count.finish()),
+            Arc::new(self.flags.finish()),
+            Arc::new(self.used_entry_size.finish()),
+            Arc::new(self.total_entry_size.finish()),
+            Arc::new(self.file_size.finish()),
+            Arc::new(self.is_a_directory.finish()),
+            Arc::new(self.is_deleted.finish()),
+            Arc::new(self.has_alternate_data_streams.finish()),
+            Arc::new(self.standard_info_flags.finish()),
+            timestamps(&mut self.standard_info_last_modified),
+            timestamps(&mut self.standard_info_last_access),
+            timestamps(&mut self.standard_info_created),
+            Arc::new(self.file_name_flags.finish()),
+            timestamps(&mut self.file_name_last_modified),
+            timestamps(&mut self.file_name_last_access),
+            timestamps(&mut self.file_name_created),
+            Arc::new(self.full_path.finish()),
+            Arc::new(self.path_status.finish()),
+            Arc::new(self.path_error.finish()),
+            Arc::new(self.full_paths.finish()),
+            Arc::new(self.full_path_namespaces.finish()),
+        ];
+
+        RecordBatch::try_new(schema, columns)
+    }
+}
+
+/// Reads the remaining entries as record batches of `batch_size` flat entries.
+/// Entries which fail to parse are skipped, with a warning.
+pub struct FlatBatchReader {
+    entries: PyMftEntriesIterator,
+    schema: SchemaRef,
+    batch_size: usize,
+}
+
+impl FlatBatchReader {
+    pub fn new(entries: PyMftEntriesIterator, batch_size: usize) -> Self {
+        FlatBatchReader {
+            entries,
+            schema: flat_schema(),
+            batch_size,
+        }
+    }
+}
+
+impl Iterator for FlatBatchReader {
+    type Item = Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut builder = FlatBatchBuilder::default();
+        let mut rows = 0;
+
+        while rows < self.batch_size {
+            let record = match self.entries.next_record() {
+                Some(Ok(record)) => record,
+                Some(Err(error)) => {
+                    warn!("skipping entry: {error}");
+                    continue;
+                }
+                None => break,
+            };
+
+            let (entry, paths) = self.entries.csv_row(&record.entry);
+            builder.append(&entry, &paths);
+            rows += 1;
+        }
+
+        if rows == 0 {
+            return None;
+        }
+
+        Some(builder.finish(self.schema.clone()))
+    }
+}
+
+impl RecordBatchReader for FlatBatchReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+/// Exports the batches of a `FlatBatchReader` through the arrow PyCapsule interface,
+/// so they can be imported by `pyarrow` (or any other arrow library) without copying.
+#[pyclass(unsendable)]
+pub struct PyMftArrowStream {
+    inner: Option<FlatBatchReader>,
+}
+
+#[pymethods]
+impl PyMftArrowStream {
+    #[pyo3(signature = (requested_schema=None))]
+    fn __arrow_c_stream__<'py>(
+        &mut self,
+        py: Python<'py>,
+        requested_schema: Option<Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyCapsule>> {
+        // Honoring the requested schema is optional, the consumer casts if it needs to.
+        let _ = requested_schema;
+
+        let reader = self
+            .inner
+            .take()
+            .ok_or_else(|| PyRuntimeError::new_err("the arrow stream can only be consumed once"))?;
+
+        PyCapsule::new(
+            py,
+            FFI_ArrowArrayStream::new(Box::new(reader)),
+            Some(CString::new("arrow_array_stream")?),
+        )
+    }
+}
+
+impl PyMftArrowStream {
+    pub fn new(reader: FlatBatchReader) -> Self {
+        PyMftArrowStream {
+            inner: Some(reader),
+        }
+    }
+}
+
+/// Writes all the batches of `reader` to a snappy compressed parquet file,
+/// returns the number of entries written.
+pub fn write_parquet(
+    reader: FlatBatchReader,
+    writer: impl Write + Send,
+) -> Result<u64, ParquetError> {
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+
+    let mut writer = ArrowWriter::try_new(writer, reader.schema(), Some(properties))?;
+    let mut written = 0;
+
+    for batch in reader {
+        let batch = batch?;
+        written += batch.num_rows() as u64;
+        writer.write(&batch)?;
+    }
+
+    writer.close()?;
+    Ok(written)
+}
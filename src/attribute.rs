@@ -12,8 +12,13 @@ use mft_rs::attribute::x20::{AttributeListAttr, AttributeListEntry};
 use pyo3::prelude::*;
 use pyo3::IntoPyObjectExt;
 
+use crate::reader::Record;
 use crate::utils::date_to_pyobject;
 
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::Arc;
+
 #[pyclass]
 pub struct PyMftAttribute {
     inner: MftAttribute,
@@ -32,10 +37,21 @@ pub struct PyMftAttribute {
     pub is_resident: bool,
     #[pyo3(get)]
     pub data_size: u32,
+    /// The record the attribute was parsed from, and the range of the attribute in it,
+    /// sliced when its raw bytes are asked for.
+    record: Arc<Record>,
+    range: Range<usize>,
 }
 
 impl PyMftAttribute {
-    pub fn from_mft_attribute(py: Python, attr: MftAttribute) -> PyResult<Py<Self>> {
+    pub fn from_mft_attribute(
+        py: Python,
+        attr: MftAttribute,
+        record: &Arc<Record>,
+    ) -> PyResult<Py<Self>> {
+        let start = attr.header.start_offset as usize;
+        let range = start..start + attr.header.record_length as usize;
+
         Py::new(
             py,
             PyMftAttribute {
@@ -50,6 +66,8 @@ impl PyMftAttribute {
                     )
                 },
                 data_size: attr.header.record_length,
+                record: Arc::clone(record),
+                range,
                 inner: attr,
             },
         )
@@ -98,6 +116,26 @@ impl PyMftAttribute {
             })
         })
     }
+
+    /// raw_bytes(self, /, fixups=False)
+    /// --
+    ///
+    /// Returns the bytes of the attribute record as found on disk, or with the update sequence
+    /// array fixups applied (as they were parsed) when `fixups` is set.
+    #[pyo3(signature = (fixups=false))]
+    pub fn raw_bytes(&self, fixups: bool) -> Cow<'_, [u8]> {
+        if fixups {
+            Cow::Borrowed(
+                self.record
+                    .entry
+                    .data
+                    .get(self.range.clone())
+                    .unwrap_or_default(),
+            )
+        } else {
+            Cow::Owned(self.record.on_disk_bytes(self.range.clone()))
+        }
+    }
 }
 
 #[pyclass]
@@ -0,0 +1,161 @@
+use crate::entry::PyMftEntry;
+use crate::path::EntryPaths;
+use crate::reader::parse_record;
+use crate::ReadSeek;
+
+use byteorder::{ByteOrder, LittleEndian};
+use mft_rs::entry::{BAAD_HEADER, FILE_HEADER};
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+
+use std::io::{self, Read, SeekFrom};
+
+/// Records are scanned for in windows of this size, to avoid a read per candidate offset.
+const WINDOW_SIZE: usize = 1024 * 1024;
+/// The largest record size in use by NTFS.
+const MAX_RECORD_SIZE: usize = 4096;
+
+/// Offset of the record number in the header (only present since NTFS 3.1).
+const RECORD_NUMBER_OFFSET: usize = 0x2C;
+/// The update sequence array follows the record number in NTFS 3.1 records.
+const NTFS_31_USA_OFFSET: usize = 0x30;
+
+#[pyclass(unsendable)]
+/// Yields `PyMftEntry` objects for the FILE (or BAAD) records found in an arbitrary blob.
+pub struct PyMftCarvedEntriesIterator {
+    inner: Box<dyn ReadSeek + Send>,
+    alignment: u64,
+    // Offset in the source of the next candidate record.
+    position: u64,
+    window: Vec<u8>,
+    // Offset in the source of the first byte of `window`.
+    window_offset: u64,
+    // Whether `window` reaches the end of the source.
+    window_is_last: bool,
+}
+
+#[pymethods]
+impl PyMftCarvedEntriesIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyMftCarvedEntriesIterator>> {
+        Ok(slf.into())
+    }
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<Py<PyAny>>> {
+        slf.next()
+    }
+}
+
+impl PyMftCarvedEntriesIterator {
+    pub fn new(inner: Box<dyn ReadSeek + Send>, alignment: u64) -> Self {
+        PyMftCarvedEntriesIterator {
+            inner,
+            alignment,
+            position: 0,
+            window: Vec::with_capacity(WINDOW_SIZE),
+            window_offset: 0,
+            window_is_last: false,
+        }
+    }
+
+    /// Makes sure a whole record starting at `self.position` is in the window
+    /// (unless the source ends before that).
+    fn fill_window(&mut self) -> io::Result<()> {
+        let window_end = self.window_offset + self.window.len() as u64;
+
+        if self.position >= self.window_offset
+            && (self.window_is_last || self.position + MAX_RECORD_SIZE as u64 <= window_end)
+        {
+            return Ok(());
+        }
+
+        self.inner.seek(SeekFrom::Start(self.position))?;
+        self.window.clear();
+        self.window_offset = self.position;
+
+        (&mut self.inner)
+            .take(WINDOW_SIZE as u64)
+            .read_to_end(&mut self.window)?;
+        self.window_is_last = self.window.len() < WINDOW_SIZE;
+
+        Ok(())
+    }
+
+    /// Returns the size of the record starting at `start` in the window,
+    /// if its header looks like a valid FILE record header.
+    fn candidate_record_size(&self, start: usize) -> Option<usize> {
+        let header = self.window.get(start..start + NTFS_31_USA_OFFSET)?;
+
+        if &header[..4] != FILE_HEADER && &header[..4] != BAAD_HEADER {
+            return None;
+        }
+
+        let usa_size = usize::from(LittleEndian::read_u16(&header[6..8]));
+        let used_entry_size = LittleEndian::read_u32(&header[0x18..0x1C]) as usize;
+        let total_entry_size = LittleEndian::read_u32(&header[0x1C..0x20]) as usize;
+
+        let plausible = total_entry_size.is_power_of_two()
+            && (1024..=MAX_RECORD_SIZE).contains(&total_entry_size)
+            && used_entry_size <= total_entry_size
+            // There is a fixup for every 512 bytes stride, following the update sequence number.
+            && usa_size == total_entry_size / 512 + 1;
+
+        if plausible {
+            Some(total_entry_size)
+        } else {
+            None
+        }
+    }
+
+    fn next(&mut self) -> PyResult<Option<Py<PyAny>>> {
+        Python::attach(|py| loop {
+            self.fill_window()?;
+
+            let start = (self.position - self.window_offset) as usize;
+
+            if start + NTFS_31_USA_OFFSET > self.window.len() {
+                return Ok(None);
+            }
+
+            let offset = self.position;
+
+            let data = match self
+                .candidate_record_size(start)
+                .and_then(|size| self.window.get(start..start + size))
+            {
+                Some(data) => data.to_vec(),
+                None => {
+                    self.position += self.alignment;
+                    continue;
+                }
+            };
+
+            let usa_offset = usize::from(LittleEndian::read_u16(&data[4..6]));
+            let entry_id = if usa_offset >= NTFS_31_USA_OFFSET {
+                u64::from(LittleEndian::read_u32(
+                    &data[RECORD_NUMBER_OFFSET..RECORD_NUMBER_OFFSET + 4],
+                ))
+            } else {
+                0
+            };
+
+            // Only records whose fixups all match are likely to be actual records.
+            let record = match parse_record(data, entry_id) {
+                Ok(record) if record.fixup_errors.is_empty() => record,
+                _ => {
+                    self.position += self.alignment;
+                    continue;
+                }
+            };
+
+            // Skip the whole record, aligned.
+            let record_size = record.entry.data.len() as u64;
+            self.position += record_size.div_ceil(self.alignment) * self.alignment;
+
+            let paths = EntryPaths::detached(&record.entry);
+
+            return match PyMftEntry::from_mft_entry(py, record, paths, Some(offset)) {
+                Ok(entry) => Ok(Some(entry.into_py_any(py)?)),
+                Err(e) => Ok(Some(e.into_py_any(py)?)),
+            };
+        })
+    }
+}
@@ -0,0 +1,147 @@
+use crate::ReadSeek;
+
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use lzma_rust2::XzReader;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use zip::{CompressionMethod, ZipArchive};
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// The size of the longest magic.
+const MAGIC_SIZE: u64 = 6;
+
+/// The name of the MFT in zip archives (e.g. triage packages), in any directory.
+const MFT_NAME: &str = "$MFT";
+
+/// The formats compressed MFTs are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    /// A zip archive containing the MFT, which can only be read from a seekable input.
+    Zip,
+}
+
+impl Compression {
+    /// Detects the compression of data from its first bytes.
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        [
+            (GZIP_MAGIC, Compression::Gzip),
+            (ZSTD_MAGIC, Compression::Zstd),
+            (XZ_MAGIC, Compression::Xz),
+            (ZIP_MAGIC, Compression::Zip),
+        ]
+        .iter()
+        .find(|(format_magic, _)| magic.starts_with(format_magic))
+        .map(|(_, compression)| *compression)
+    }
+}
+
+/// Reads the first bytes of `data`, enough to detect its compression.
+pub fn read_magic(data: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut magic = Vec::new();
+    data.take(MAGIC_SIZE).read_to_end(&mut magic)?;
+
+    Ok(magic)
+}
+
+/// Returns a reader of the decompressed data, or of the data itself if it isn't compressed.
+pub fn decompress(mut data: Box<dyn Read + Send>) -> PyResult<Box<dyn Read + Send>> {
+    let magic = read_magic(&mut data)?;
+    let compression = Compression::detect(&magic);
+
+    // Streams can't seek back, so the magic is put back in front of them instead.
+    let data = Box::new(Cursor::new(magic).chain(data));
+
+    Ok(match compression {
+        None => data,
+        Some(Compression::Gzip) => Box::new(MultiGzDecoder::new(data)),
+        Some(Compression::Zstd) => Box::new(zstd::stream::read::Decoder::new(data)?),
+        Some(Compression::Xz) => Box::new(XzReader::new(data, true)),
+        Some(Compression::Zip) => {
+            return Err(PyValueError::new_err(
+                "zip archives can only be read from a seekable input",
+            ))
+        }
+    })
+}
+
+/// Returns a reader of the MFT in a zip archive: the file named `$MFT` (in any directory),
+/// or the only file of the archive.
+pub fn unzip(data: Box<dyn ReadSeek + Send>) -> PyResult<Box<dyn Read + Send>> {
+    let zip_error = |e| PyValueError::new_err(format!("failed to read the zip archive: {e}"));
+    let mut archive = ZipArchive::new(data).map_err(zip_error)?;
+
+    let mut files = vec![];
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index).map_err(zip_error)?;
+        if file.is_file() {
+            files.push((index, file.name().to_string()));
+        }
+    }
+
+    let is_mft = |name: &str| {
+        name.rsplit(['/', '\\'])
+            .next()
+            .is_some_and(|file_name| file_name.eq_ignore_ascii_case(MFT_NAME))
+    };
+
+    let mfts: Vec<_> = files.iter().filter(|(_, name)| is_mft(name)).collect();
+    let index = match (mfts.as_slice(), files.as_slice()) {
+        ([(index, _)], _) | ([], [(index, _)]) => *index,
+        ([], _) => {
+            return Err(PyValueError::new_err(
+                "the zip archive does not contain a $MFT file",
+            ))
+        }
+        _ => {
+            let names: Vec<_> = mfts.iter().map(|(_, name)| name.as_str()).collect();
+            return Err(PyValueError::new_err(format!(
+                "the zip archive contains several $MFT files: {}",
+                names.join(", ")
+            )));
+        }
+    };
+
+    // Files are decompressed here (instead of by `ZipFile`, which borrows the archive),
+    // so the reader can outlive the archive.
+    let file = archive.by_index_raw(index).map_err(zip_error)?;
+    let (compression, data_start, compressed_size) = (
+        file.compression(),
+        file.data_start(),
+        file.compressed_size(),
+    );
+
+    if file.encrypted() {
+        return Err(PyValueError::new_err(format!(
+            "{} is encrypted in the zip archive",
+            file.name()
+        )));
+    }
+
+    drop(file);
+
+    let mut data = archive.into_inner();
+    data.seek(SeekFrom::Start(data_start))?;
+    let data = data.take(compressed_size);
+
+    // The decoders of `zip` are disabled, so compression methods are only known by their id.
+    Ok(if compression == CompressionMethod::STORE {
+        Box::new(data)
+    } else if compression == CompressionMethod::DEFLATE {
+        Box::new(DeflateDecoder::new(data))
+    } else if compression == CompressionMethod::ZSTD {
+        Box::new(zstd::stream::read::Decoder::new(data)?)
+    } else {
+        return Err(PyValueError::new_err(format!(
+            "unsupported zip compression method: {compression}"
+        )));
+    })
+}
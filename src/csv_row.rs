@@ -0,0 +1,239 @@
+use csv::{Error, Result};
+use serde::ser::{self, Error as _, Impossible, Serialize};
+
+/// Collects the fields of a CSV row as strings, formatted the same way `csv::Writer::serialize`
+/// formats them, so some of them can be picked (or replaced) before writing the row.
+pub fn row_fields<T: Serialize>(row: &T) -> Result<Vec<String>> {
+    let mut fields = RowFields(Vec::new());
+    row.serialize(&mut fields)?;
+
+    Ok(fields.0)
+}
+
+struct RowFields(Vec<String>);
+
+impl RowFields {
+    fn push(&mut self, field: impl ToString) -> Result<()> {
+        self.0.push(field.to_string());
+        Ok(())
+    }
+}
+
+impl ser::Serializer for &mut RowFields {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.push(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.push(String::from_utf8_lossy(v))
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.push("")
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.push("")
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<()> {
+        self.push(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.push(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(Error::custom(
+            "serializing enum tuple variants is not supported",
+        ))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Error::custom("serializing maps is not supported"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(Error::custom(
+            "serializing enum struct variants is not supported",
+        ))
+    }
+}
+
+impl ser::SerializeSeq for &mut RowFields {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut RowFields {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut RowFields {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut RowFields {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
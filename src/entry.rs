@@ -1,18 +1,23 @@
-use crate::ReadSeek;
 use pyo3::prelude::*;
 use pyo3::IntoPyObjectExt;
 
+use crate::anomaly::{timestamp_anomalies, PyMftTimestampAnomaly};
 use crate::attribute::PyMftAttribute;
 use crate::err::PyMftError;
-use mft_rs::attribute::header::ResidentialHeader;
-use mft_rs::attribute::MftAttributeType;
-use mft_rs::{MftEntry, MftParser};
-use std::path::PathBuf;
+use crate::output::data_size;
+use crate::path::{EntryPaths, PyMftEntryPath};
+use crate::reader::{PyMftFixupError, Record};
+use crate::slack::{residual_attributes, slack};
+use mft_rs::MftAttribute;
+use std::borrow::Cow;
+use std::iter;
+use std::sync::Arc;
 
 #[pyclass]
 pub struct PyMftEntry {
     // We need to keep inner entry to access it's attributes.
-    inner: MftEntry,
+    // It is shared with the attributes, for their raw bytes.
+    inner: Arc<Record>,
     #[pyo3(get)]
     pub entry_id: u64,
     #[pyo3(get)]
@@ -29,27 +34,119 @@ pub struct PyMftEntry {
     pub used_entry_size: u32,
     #[pyo3(get)]
     pub total_entry_size: u32,
+    /// `None` if the entry has nothing to resolve a path from (no $FILE_NAME attribute),
+    /// like `null` in JSON output and an empty `FullPath` in CSV output.
     #[pyo3(get)]
-    pub full_path: String,
+    pub full_path: Option<String>,
+    /// How `full_path` was resolved, one of
+    /// `resolved`, `orphan`, `sequence-mismatch`, `loop`, `unknown` or `error`.
+    #[pyo3(get)]
+    pub path_status: String,
+    /// Why resolving the paths failed (if `path_status` is `error`).
+    #[pyo3(get)]
+    pub path_error: Option<String>,
+    /// A full path for every $FILE_NAME attribute (hard links, `Win32` and `DOS` names).
+    #[pyo3(get)]
+    pub full_paths: Vec<PyMftEntryPath>,
     #[pyo3(get)]
     pub file_size: u64,
+    /// Offset of the record in the source it was read from (`None` for `parse_entry`).
+    #[pyo3(get)]
+    pub offset: Option<u64>,
+    /// Whether the record has a FILE signature, and all of its fixups match.
+    #[pyo3(get)]
+    pub valid: bool,
+    /// The strides of the record whose fixups don't match.
+    #[pyo3(get)]
+    pub fixup_errors: Vec<PyMftFixupError>,
+    /// Whether the record was never used (zeroed), see `include_empty`.
+    #[pyo3(get)]
+    pub empty: bool,
 }
 
 #[pymethods]
 impl PyMftEntry {
     pub fn attributes(&self) -> PyResult<Py<PyMftAttributesIter>> {
+        // Empty records have no attributes to iterate over.
+        if self.empty {
+            return self.attributes_iter(iter::empty());
+        }
+
+        self.attributes_iter(self.inner.entry.iter_attributes())
+    }
+
+    /// The bytes of the record after its used part (between `used_entry_size` and `total_entry_size`).
+    #[getter]
+    pub fn slack(&self) -> &[u8] {
+        slack(&self.inner.entry.data, self.used_entry_size)
+    }
+
+    /// slack_attributes(self, /)
+    /// --
+    ///
+    /// Returns an iterator over the residual resident $FILE_NAME and $DATA attributes
+    /// found in the slack of the record, such as the names a file had before it was renamed.
+    /// Only attributes that parse successfully are yielded, but they may still be partially
+    /// overwritten, so their content should be treated with care.
+    pub fn slack_attributes(&self) -> PyResult<Py<PyMftAttributesIter>> {
+        self.attributes_iter(
+            residual_attributes(&self.inner.entry.data, self.used_entry_size)
+                .into_iter()
+                .map(Ok),
+        )
+    }
+
+    /// timestamp_anomalies(self, /)
+    /// --
+    ///
+    /// Returns the $STANDARD_INFORMATION timestamps of the entry which look like they were
+    /// tampered with (timestomped), as a list of `PyMftTimestampAnomaly`. Anomalies are:
+    /// - `created-before-file-name`: created is earlier than the created timestamp of a $FILE_NAME attribute.
+    /// - `zero-subsecond`: the timestamp has no sub-second part.
+    /// - `after-mft-modified`: created or modified is later than mft_modified.
+    pub fn timestamp_anomalies(&self) -> Vec<PyMftTimestampAnomaly> {
+        timestamp_anomalies(&self.inner.entry)
+    }
+
+    /// raw_bytes(self, /, fixups=False)
+    /// --
+    ///
+    /// Returns the bytes of the record as found on disk, or with the update sequence
+    /// array fixups applied (as they were parsed) when `fixups` is set.
+    #[pyo3(signature = (fixups=false))]
+    pub fn raw_bytes(&self, fixups: bool) -> Cow<'_, [u8]> {
+        if fixups {
+            Cow::Borrowed(&self.inner.entry.data)
+        } else {
+            Cow::Owned(self.inner.on_disk_bytes(0..self.inner.entry.data.len()))
+        }
+    }
+}
+
+impl PyMftEntry {
+    fn attributes_iter(
+        &self,
+        attribute_results: impl Iterator<Item = mft_rs::err::Result<MftAttribute>>,
+    ) -> PyResult<Py<PyMftAttributesIter>> {
         Python::attach(|py| {
             let mut attributes = vec![];
 
-            for attribute_result in self.inner.iter_attributes() {
+            for attribute_result in attribute_results {
                 match attribute_result {
-                    Ok(attribute) => match PyMftAttribute::from_mft_attribute(py, attribute)
-                        .and_then(|entry| entry.into_py_any(py))
-                    {
-                        Ok(obj) => attributes.push(obj),
-                        Err(e) => attributes.push(e.into_py_any(py).unwrap()),
-                    },
-                    Err(e) => attributes.push(PyErr::from(PyMftError(e)).into_py_any(py).unwrap()),
+                    Ok(attribute) => {
+                        match PyMftAttribute::from_mft_attribute(py, attribute, &self.inner)
+                            .and_then(|entry| entry.into_py_any(py))
+                        {
+                            Ok(obj) => attributes.push(obj),
+                            Err(e) => attributes.push(e.into_py_any(py).unwrap()),
+                        }
+                    }
+                    Err(e) => attributes.push(
+                        PyMftError(e)
+                            .for_entry(self.entry_id)
+                            .into_py_any(py)
+                            .unwrap(),
+                    ),
                 }
             }
 
@@ -61,28 +158,21 @@ impl PyMftEntry {
             )
         })
     }
-}
 
-impl PyMftEntry {
     pub fn from_mft_entry(
         py: Python,
-        entry: MftEntry,
-        parser: &mut MftParser<impl ReadSeek>,
+        record: Record,
+        paths: EntryPaths,
+        offset: Option<u64>,
     ) -> PyResult<Py<PyMftEntry>> {
-        let full_path = parser
-            .get_full_path_for_entry(&entry)
-            .expect("unreachable")
-            .unwrap_or_else(|| PathBuf::from("[UNKNOWN]"))
-            .to_string_lossy()
-            .to_string();
-
-        let file_size = entry
-            .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
-            .find_map(Result::ok)
-            .map_or(0, |attr| match &attr.header.residential_header {
-                ResidentialHeader::Resident(r) => u64::from(r.data_size),
-                ResidentialHeader::NonResident(nr) => nr.file_size,
-            });
+        let valid = record.is_valid();
+        let empty = record.is_empty();
+        let entry = &record.entry;
+
+        let path_status = paths.status().to_string();
+        let full_path = paths
+            .full_path
+            .map(|p| p.path.to_string_lossy().to_string());
 
         Py::new(
             py,
@@ -95,9 +185,16 @@ impl PyMftEntry {
                 flags: format!("{:?}", entry.header.flags),
                 used_entry_size: entry.header.used_entry_size,
                 total_entry_size: entry.header.total_entry_size,
-                inner: entry,
                 full_path,
-                file_size,
+                path_status,
+                path_error: paths.error,
+                full_paths: paths.full_paths,
+                file_size: data_size(entry),
+                offset,
+                valid,
+                fixup_errors: record.fixup_errors.clone(),
+                empty,
+                inner: Arc::new(record),
             },
         )
     }
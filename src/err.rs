@@ -1,3 +1,4 @@
+use pyo3::prelude::*;
 use pyo3::{exceptions, PyErr};
 
 pub struct PyMftError(pub mft_rs::err::Error);
@@ -10,3 +11,27 @@ impl From<PyMftError> for PyErr {
         }
     }
 }
+
+impl PyMftError {
+    /// Converts the error raised for an entry, with the id of the entry in its message
+    /// and in its `entry_id` attribute.
+    pub fn for_entry(self, entry_id: u64) -> PyErr {
+        let err = match self.0 {
+            mft_rs::err::Error::IoError { source } => source.into(),
+            error => {
+                PyErr::new::<exceptions::PyRuntimeError, _>(format!("entry {entry_id}: {error}"))
+            }
+        };
+
+        with_entry_id(err, entry_id)
+    }
+}
+
+/// Sets the `entry_id` attribute of an exception raised for an entry.
+pub fn with_entry_id(err: PyErr, entry_id: u64) -> PyErr {
+    Python::attach(|py| {
+        // Built-in exceptions accept any attribute, this can't fail.
+        let _ = err.value(py).setattr("entry_id", entry_id);
+        err
+    })
+}
@@ -1,31 +1,56 @@
 #![deny(unused_must_use)]
 #![cfg_attr(not(debug_assertions), deny(clippy::dbg_macro))]
 
+mod anomaly;
+mod arrow;
 mod attribute;
+mod carve;
+mod compression;
+mod csv_row;
 mod entry;
+mod output;
+mod parallel;
+mod path;
+mod reader;
+mod serializer;
+mod slack;
+mod stream;
 mod utils;
 
 pub(crate) mod err;
 pub use entry::PyMftEntry;
+use log::warn;
 use mft_rs::csv::FlatMftEntryWithName;
 use mft_rs::entry::ZERO_HEADER;
-use mft_rs::{MftEntry, MftParser};
+use mft_rs::MftEntry;
 
 use std::fs::File;
 use std::io;
-use std::io::{BufReader, Read, Seek};
+use std::io::{BufWriter, Read, Seek, Write};
+use std::ops::Range;
+use std::path::PathBuf;
 
 use pyo3::exceptions;
 use pyo3::prelude::*;
 use pyo3::IntoPyObjectExt;
 
+use crate::anomaly::PyMftTimestampAnomaly;
+use crate::arrow::{write_parquet, FlatBatchReader, PyMftArrowStream};
 use crate::attribute::{
     PyMftAttribute, PyMftAttributeOther, PyMftAttributeX10, PyMftAttributeX20, PyMftAttributeX30,
     PyMftAttributeX40, PyMftAttributeX80, PyMftAttributeX90,
 };
+use crate::carve::PyMftCarvedEntriesIterator;
+use crate::compression::{decompress, unzip, Compression};
 use crate::entry::PyMftAttributesIter;
-use crate::err::PyMftError;
-use crate::utils::{init_logging, FileOrFileLike};
+use crate::err::{with_entry_id, PyMftError};
+use crate::output::{
+    bodyfile_lines, flat_row, timeline_rows, CsvOptions, FlatMftEntryPaths, JsonOptions,
+};
+use crate::parallel::write_parallel;
+use crate::path::{EntryPaths, PathOptions, PathResolver, PyMftEntryPath};
+use crate::reader::{parse_record, MftReader, PyMftFixupError, Record};
+use crate::utils::{init_logging, FileOptions, FileOrFileLike, DEFAULT_BUFFER_SIZE};
 use csv::WriterBuilder;
 use pyo3::types::{PyBytes, PyString};
 
@@ -37,44 +62,114 @@ pub trait ReadSeek: Read + Seek {
 
 impl<T: Read + Seek> ReadSeek for T {}
 
+#[derive(Clone, Copy)]
 pub enum Output {
     Python,
     CSV,
     JSON,
+    Dict,
+    Bodyfile,
+    Timeline,
 }
 
 #[pyclass(unsendable)]
-/// PyMftParser(self, path_or_file_like, /)
+/// PyMftParser(self, path_or_file_like, /, *, verify_parent_sequence=False, mark_orphans=False, mmap=False, buffer_size=4096, stream=False)
 /// --
 ///
 /// Returns an instance of the parser.
-/// Works on both a path (string), or a file-like object.
+/// Works on a path (string or `os.PathLike`), data in memory (`bytes`, `bytearray`, `memoryview`,
+/// or any other object supporting the buffer protocol), or a file-like object.
+/// Read-only data in memory (such as `bytes`) is read in place, other data is copied first.
+///
+/// When `verify_parent_sequence` is set, a parent reference whose sequence number doesn't match
+/// the parent entry is treated as stale, since the parent entry was reused.
+/// When `mark_orphans` is set, entries whose parent cannot be resolved are placed under `$OrphanFiles`.
+///
+/// A path is read through a buffer of `buffer_size` bytes, or memory mapped when `mmap` is set,
+/// which saves a system call per record. The file must not be modified while it is memory mapped.
+///
+/// A file-like object which cannot seek (a pipe, a socket...) is read as a stream, and so is any
+/// input when `stream` is set (e.g. `gzip.open` handles, which seek by decompressing again).
+/// Entries of a stream are read once, in order, which is all iteration needs. To resolve paths,
+/// directories are kept in memory, and a parent which comes after its children is read ahead
+/// (keeping the entries in between in memory until they are iterated over). Parents more than
+/// 16 MiB of entries ahead are not read, and their children are orphans.
+/// The number of entries of a stream is unknown, and `threads` cannot be used.
+///
+/// Data compressed with gzip, zstd or xz is decompressed as it is read (as a stream), and so is
+/// the `$MFT` file of a zip archive (or its only file). Compression is detected from the data.
 pub struct PyMftParser {
-    inner: Option<MftParser<Box<dyn ReadSeek + Send>>>,
+    inner: Option<MftReader<Box<dyn ReadSeek + Send>>>,
+    path_options: PathOptions,
+    /// Whether the GIL can be released while reading (the input is not a python file-like object).
+    release_gil: bool,
+    /// Where the MFT was read from, if it can be read again (a path or data in memory,
+    /// not read as a stream), so it can be read by worker threads.
+    source: Option<FileOrFileLike>,
+    file_options: FileOptions,
 }
 
 #[pymethods]
 impl PyMftParser {
     #[new]
-    fn new(path_or_file_like: Py<PyAny>) -> PyResult<Self> {
-        let file_or_file_like = FileOrFileLike::from_pyobject(path_or_file_like)?;
+    #[pyo3(signature = (path_or_file_like, /, *, verify_parent_sequence=false, mark_orphans=false, mmap=false, buffer_size=DEFAULT_BUFFER_SIZE, stream=false))]
+    fn new(
+        path_or_file_like: Py<PyAny>,
+        verify_parent_sequence: bool,
+        mark_orphans: bool,
+        mmap: bool,
+        buffer_size: usize,
+        stream: bool,
+    ) -> PyResult<Self> {
+        let file_options = FileOptions::new(mmap, buffer_size)?;
+        let mut source = FileOrFileLike::from_pyobject(path_or_file_like)?;
 
-        let (boxed_read_seek, size) = match file_or_file_like {
-            FileOrFileLike::File(s) => {
-                let file = File::open(s)?;
-                let size = file.metadata()?.len();
+        if mmap && !matches!(source, FileOrFileLike::File(_)) {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "mmap can only be used when parsing a path",
+            ));
+        }
 
-                let reader = BufReader::with_capacity(4096, file);
+        // Compressed data is decompressed as it is read, so it is read as a stream.
+        let compression = source.compression()?;
+        let stream = stream || compression.is_some() || matches!(source, FileOrFileLike::Stream(_));
 
-                (Box::new(reader) as Box<dyn ReadSeek + Send>, Some(size))
-            }
-            FileOrFileLike::FileLike(f) => (Box::new(f) as Box<dyn ReadSeek + Send>, None),
-        };
+        if mmap && stream {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "mmap cannot be used with a stream or compressed data",
+            ));
+        }
+
+        // Python file-like objects need the GIL for every read.
+        let release_gil = !matches!(
+            source,
+            FileOrFileLike::FileLike(_) | FileOrFileLike::Stream(_)
+        );
+
+        let (parser, reopen) = if stream {
+            let read = match compression {
+                Some(Compression::Zip) => unzip(source.into_read_seek(file_options)?.0)?,
+                _ => decompress(source.into_read(file_options)?)?,
+            };
 
-        let parser = MftParser::from_read_seek(boxed_read_seek, size).map_err(PyMftError)?;
+            (MftReader::from_stream(read).map_err(PyMftError)?, None)
+        } else {
+            let reopen = source.try_clone();
+            let (boxed_read_seek, size) = source.into_read_seek(file_options)?;
+            let parser = MftReader::from_read_seek(boxed_read_seek, size).map_err(PyMftError)?;
+
+            (parser, reopen)
+        };
 
         Ok(PyMftParser {
             inner: Some(parser),
+            path_options: PathOptions {
+                verify_parent_sequence,
+                mark_orphans,
+            },
+            release_gil,
+            source: reopen,
+            file_options,
         })
     }
 
@@ -84,39 +179,335 @@ impl PyMftParser {
     /// Returns the total number of entries in the MFT.
     fn number_of_entries(&self) -> PyResult<u64> {
         match self.inner {
-            Some(ref inner) => Ok(inner.get_entry_count()),
+            Some(ref inner) => inner.get_entry_count().ok_or_else(|| {
+                PyErr::new::<exceptions::PyRuntimeError, _>(
+                    "The number of entries of a stream is unknown until it is read",
+                )
+            }),
             None => Err(PyErr::new::<exceptions::PyRuntimeError, _>(
                 "Cannot call this method before object is initialized",
             )),
         }
     }
 
-    /// entries(self, /)
+    /// entries(self, /, *, strict=False, include_empty=False)
     /// --
     ///
     /// Returns an iterator that yields the mft entries as python objects.
-    fn entries(&mut self) -> PyResult<Py<PyMftEntriesIterator>> {
-        self.records_iterator(Output::Python)
+    ///
+    /// Records with a BAAD signature, or whose fixups don't match (torn writes), are yielded as
+    /// entries, with `valid` set to False, and the mismatching strides in `fixup_errors`.
+    /// If `strict` is set, they are yielded as errors instead.
+    ///
+    /// Records that were never used (zeroed) are skipped, unless `include_empty` is set.
+    /// In that case a placeholder entry is yielded for them, with `empty` set to True.
+    #[pyo3(signature = (*, strict=false, include_empty=false))]
+    fn entries(&mut self, strict: bool, include_empty: bool) -> PyResult<Py<PyMftEntriesIterator>> {
+        self.records_iterator(Output::Python, strict, include_empty)
+    }
+
+    /// entries_batched(self, /, *, batch_size=10000, strict=False, include_empty=False)
+    /// --
+    ///
+    /// Returns an iterator that yields lists of up to `batch_size` mft entries, the same as those
+    /// yielded by `entries` (including errors), which is cheaper than yielding them one at a time.
+    /// See `entries` for `strict` and `include_empty`.
+    #[pyo3(signature = (*, batch_size=10000, strict=false, include_empty=false))]
+    fn entries_batched(
+        &mut self,
+        batch_size: usize,
+        strict: bool,
+        include_empty: bool,
+    ) -> PyResult<Py<PyMftEntriesBatchIterator>> {
+        if batch_size == 0 {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "batch_size must be positive",
+            ));
+        }
+
+        let iterator = self.take_records_iterator(Output::Python, strict, include_empty)?;
+
+        Python::attach(|py| {
+            Py::new(
+                py,
+                PyMftEntriesBatchIterator {
+                    inner: iterator,
+                    batch_size,
+                },
+            )
+        })
     }
 
-    /// entries_json(self, /)
+    /// entries_json(self, /, *, strict=False, include_empty=False, pretty=False, flat=False, fields=None)
     /// --
     ///
     /// Returns an iterator that yields mft entries as JSON.
-    fn entries_json(&mut self) -> PyResult<Py<PyMftEntriesIterator>> {
-        self.records_iterator(Output::JSON)
+    /// See `entries` for `strict` and `include_empty`.
+    ///
+    /// `pretty` indents the JSON.
+    /// `flat` outputs the columns of `entries_csv` (with the resolved `FullPath`) instead of the nested entry,
+    /// which holds every attribute, including raw data.
+    /// `fields` selects which top level fields to output, and in which order (all of them by default).
+    #[pyo3(signature = (*, strict=false, include_empty=false, pretty=false, flat=false, fields=None))]
+    fn entries_json(
+        &mut self,
+        strict: bool,
+        include_empty: bool,
+        pretty: bool,
+        flat: bool,
+        fields: Option<Vec<String>>,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let json_options = JsonOptions::new(pretty, flat, fields)?;
+
+        let mut iterator = self.take_records_iterator(Output::JSON, strict, include_empty)?;
+        iterator.json_options = json_options;
+
+        Python::attach(|py| Py::new(py, iterator))
+    }
+
+    /// entries_dict(self, /, *, strict=False, include_empty=False, flat=False, fields=None)
+    /// --
+    ///
+    /// Returns an iterator that yields mft entries as python dicts, the same as `json.loads` of the
+    /// entries yielded by `entries_json`, but without serializing them to JSON strings in between.
+    /// See `entries` for `strict` and `include_empty`, and `entries_json` for the other options.
+    #[pyo3(signature = (*, strict=false, include_empty=false, flat=false, fields=None))]
+    fn entries_dict(
+        &mut self,
+        strict: bool,
+        include_empty: bool,
+        flat: bool,
+        fields: Option<Vec<String>>,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let json_options = JsonOptions::new(false, flat, fields)?;
+
+        let mut iterator = self.take_records_iterator(Output::Dict, strict, include_empty)?;
+        iterator.json_options = json_options;
+
+        Python::attach(|py| Py::new(py, iterator))
     }
 
-    /// entries_csv(self, /)
+    /// entries_csv(self, /, *, strict=False, include_empty=False, columns=None, delimiter=',', quoting='minimal', timestamp_format=None, header=True)
     /// --
     ///
     /// Returns an iterator that yields mft entries CSV lines.
-    fn entries_csv(&mut self) -> PyResult<Py<PyMftEntriesIterator>> {
-        self.records_iterator(Output::CSV)
+    /// See `entries` for `strict` and `include_empty`.
+    ///
+    /// `columns` selects which columns to output, and in which order (all of them by default).
+    /// `quoting` is one of `minimal`, `all`, `nonnumeric` or `none`, like the constants of the `csv` module.
+    /// `timestamp_format` is a `strftime` format for timestamps, which are RFC 3339 by default.
+    /// `header` controls whether the first line is a header.
+    #[pyo3(signature = (*, strict=false, include_empty=false, columns=None, delimiter=",", quoting="minimal", timestamp_format=None, header=true))]
+    #[allow(clippy::too_many_arguments)]
+    fn entries_csv(
+        &mut self,
+        strict: bool,
+        include_empty: bool,
+        columns: Option<Vec<String>>,
+        delimiter: &str,
+        quoting: &str,
+        timestamp_format: Option<String>,
+        header: bool,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let csv_options = CsvOptions::new(columns, delimiter, quoting, timestamp_format, header)?;
+
+        let mut iterator = self.take_records_iterator(Output::CSV, strict, include_empty)?;
+        iterator.csv_options = csv_options;
+
+        Python::attach(|py| Py::new(py, iterator))
+    }
+
+    /// entries_bodyfile(self, /, *, strict=False)
+    /// --
+    ///
+    /// Returns an iterator that yields the Sleuth Kit bodyfile (mactime) lines of mft entries,
+    /// one for the $STANDARD_INFORMATION timestamps, and one for every $FILE_NAME attribute.
+    /// Entries without any of these attributes (such as records that were never used) are skipped.
+    /// See `entries` for `strict`.
+    #[pyo3(signature = (*, strict=false))]
+    fn entries_bodyfile(&mut self, strict: bool) -> PyResult<Py<PyMftEntriesIterator>> {
+        self.records_iterator(Output::Bodyfile, strict, false)
+    }
+
+    /// entries_timeline(self, /, *, strict=False)
+    /// --
+    ///
+    /// Returns an iterator that yields timeline CSV lines, with a row for every timestamp of every
+    /// $STANDARD_INFORMATION and $FILE_NAME attribute, in MACB notation.
+    /// Entries without any of these attributes (such as records that were never used) are skipped.
+    /// See `entries` for `strict`.
+    #[pyo3(signature = (*, strict=false))]
+    fn entries_timeline(&mut self, strict: bool) -> PyResult<Py<PyMftEntriesIterator>> {
+        self.records_iterator(Output::Timeline, strict, false)
+    }
+
+    /// to_csv(self, path, /, *, strict=False, include_empty=False, columns=None, delimiter=',', quoting='minimal', timestamp_format=None, header=True, threads=1)
+    /// --
+    ///
+    /// Writes all the mft entries to a CSV file at `path`, without creating python objects for them,
+    /// and returns the number of entries written.
+    /// Entries which fail to parse are skipped, with a warning.
+    /// See `entries` for `strict` and `include_empty`, and `entries_csv` for the other options.
+    ///
+    /// When `threads` is more than 1, entries are parsed by that many threads, each reading the MFT
+    /// on its own, and written in the same order. This is not supported for file-like objects or streams.
+    #[pyo3(signature = (path, /, *, strict=false, include_empty=false, columns=None, delimiter=",", quoting="minimal", timestamp_format=None, header=true, threads=1))]
+    #[allow(clippy::wrong_self_convention, clippy::too_many_arguments)]
+    fn to_csv(
+        &mut self,
+        path: PathBuf,
+        strict: bool,
+        include_empty: bool,
+        columns: Option<Vec<String>>,
+        delimiter: &str,
+        quoting: &str,
+        timestamp_format: Option<String>,
+        header: bool,
+        threads: usize,
+    ) -> PyResult<u64> {
+        let csv_options = CsvOptions::new(columns, delimiter, quoting, timestamp_format, header)?;
+        self.check_threads(threads)?;
+
+        // Created first, so that the parser isn't consumed if the file can't be created.
+        let file = File::create(path)?;
+
+        let mut iterator = self.take_records_iterator(Output::CSV, strict, include_empty)?;
+        iterator.csv_options = csv_options;
+
+        let workers = self.workers(&iterator, threads)?;
+
+        Python::attach(|py| {
+            allow_threads(py, self.release_gil, || {
+                if workers.is_empty() {
+                    return iterator.write_csv(BufWriter::new(file));
+                }
+
+                let mut writer = iterator.csv_options.writer(BufWriter::new(file));
+                iterator
+                    .csv_options
+                    .write_header(&mut writer)
+                    .map_err(|e| PyErr::new::<exceptions::PyIOError, _>(e.to_string()))?;
+                let writer = writer
+                    .into_inner()
+                    .map_err(|e| PyErr::new::<exceptions::PyIOError, _>(e.to_string()))?;
+
+                write_parallel(iterator.records(), workers, writer, |worker, chunk| {
+                    let mut writer = worker.csv_options.writer(chunk);
+                    let written = worker.write_csv_rows(&mut writer)?;
+
+                    writer.flush()?;
+                    Ok(written)
+                })
+            })
+        })
+    }
+
+    /// to_jsonl(self, path, /, *, strict=False, include_empty=False, flat=False, fields=None, threads=1)
+    /// --
+    ///
+    /// Writes all the mft entries to a JSON lines file at `path` (an `entries_json` entry per line),
+    /// without creating python objects for them, and returns the number of entries written.
+    /// Entries which fail to parse are skipped, with a warning.
+    /// See `entries` for `strict` and `include_empty`, `entries_json` for the other options,
+    /// and `to_csv` for `threads`.
+    #[pyo3(signature = (path, /, *, strict=false, include_empty=false, flat=false, fields=None, threads=1))]
+    #[allow(clippy::wrong_self_convention)]
+    fn to_jsonl(
+        &mut self,
+        path: PathBuf,
+        strict: bool,
+        include_empty: bool,
+        flat: bool,
+        fields: Option<Vec<String>>,
+        threads: usize,
+    ) -> PyResult<u64> {
+        // Pretty printing isn't offered, since every entry has to fit on a line.
+        let json_options = JsonOptions::new(false, flat, fields)?;
+        self.check_threads(threads)?;
+
+        // Created first, so that the parser isn't consumed if the file can't be created.
+        let file = File::create(path)?;
+
+        let mut iterator = self.take_records_iterator(Output::JSON, strict, include_empty)?;
+        iterator.json_options = json_options;
+
+        let workers = self.workers(&iterator, threads)?;
+
+        Python::attach(|py| {
+            allow_threads(py, self.release_gil, || {
+                if workers.is_empty() {
+                    return iterator.write_jsonl(BufWriter::new(file));
+                }
+
+                write_parallel(
+                    iterator.records(),
+                    workers,
+                    BufWriter::new(file),
+                    |worker, chunk| worker.write_jsonl(chunk),
+                )
+            })
+        })
+    }
+
+    /// to_arrow(self, /, *, strict=False, include_empty=False, batch_size=65536)
+    /// --
+    ///
+    /// Returns a `pyarrow.RecordBatchReader` over all the mft entries, with the columns of
+    /// `entries_csv` (with their actual types), in batches of `batch_size` entries.
+    /// Entries are read as batches are consumed, without creating python objects for them.
+    /// Entries which fail to parse are skipped, with a warning.
+    /// See `entries` for `strict` and `include_empty`.
+    #[pyo3(signature = (*, strict=false, include_empty=false, batch_size=65536))]
+    #[allow(clippy::wrong_self_convention)]
+    fn to_arrow(
+        &mut self,
+        py: Python,
+        strict: bool,
+        include_empty: bool,
+        batch_size: usize,
+    ) -> PyResult<Py<PyAny>> {
+        // Imported first, so that the parser isn't consumed if pyarrow isn't installed.
+        let record_batch_reader = py.import("pyarrow")?.getattr("RecordBatchReader")?;
+        let reader = self.flat_batch_reader(strict, include_empty, batch_size)?;
+
+        let stream = Py::new(py, PyMftArrowStream::new(reader))?;
+
+        Ok(record_batch_reader
+            .call_method1("from_stream", (stream,))?
+            .unbind())
+    }
+
+    /// to_parquet(self, path, /, *, strict=False, include_empty=False, batch_size=65536)
+    /// --
+    ///
+    /// Writes all the mft entries to a (snappy compressed) parquet file at `path`, with the same
+    /// schema as `to_arrow`, and returns the number of entries written.
+    /// Entries which fail to parse are skipped, with a warning.
+    /// See `entries` for `strict` and `include_empty`.
+    #[pyo3(signature = (path, /, *, strict=false, include_empty=false, batch_size=65536))]
+    #[allow(clippy::wrong_self_convention)]
+    fn to_parquet(
+        &mut self,
+        path: PathBuf,
+        strict: bool,
+        include_empty: bool,
+        batch_size: usize,
+    ) -> PyResult<u64> {
+        // Created first, so that the parser isn't consumed if the file can't be created.
+        let file = File::create(path)?;
+
+        let reader = self.flat_batch_reader(strict, include_empty, batch_size)?;
+
+        Python::attach(|py| {
+            allow_threads(py, self.release_gil, || {
+                write_parquet(reader, file)
+                    .map_err(|e| PyErr::new::<exceptions::PyIOError, _>(e.to_string()))
+            })
+        })
     }
 
     fn __iter__(mut slf: PyRefMut<Self>) -> PyResult<Py<PyMftEntriesIterator>> {
-        slf.entries()
+        slf.entries(false, false)
     }
     fn __next__(_slf: PyRefMut<Self>) -> PyResult<Option<Py<PyAny>>> {
         Err(PyErr::new::<exceptions::PyNotImplementedError, _>("Using `next()` over `PyMftParser` is not supported. Try iterating over `PyMftParser(...).entries()`"))
@@ -124,40 +515,136 @@ impl PyMftParser {
 }
 
 impl PyMftParser {
-    fn records_iterator(&mut self, output_format: Output) -> PyResult<Py<PyMftEntriesIterator>> {
-        Python::attach(|py| {
-            let inner = match self.inner.take() {
-                Some(inner) => inner,
-                None => {
-                    return Err(PyErr::new::<exceptions::PyRuntimeError, _>(
-                        "PyMftParser can only be used once",
-                    ));
-                }
-            };
+    fn take_records_iterator(
+        &mut self,
+        output_format: Output,
+        strict: bool,
+        include_empty: bool,
+    ) -> PyResult<PyMftEntriesIterator> {
+        let inner = match self.inner.take() {
+            Some(inner) => inner,
+            None => {
+                return Err(PyErr::new::<exceptions::PyRuntimeError, _>(
+                    "PyMftParser can only be used once",
+                ));
+            }
+        };
 
-            let n_records = inner.get_entry_count();
+        // Streams are read until they end.
+        let n_records = inner.get_entry_count().unwrap_or(u64::MAX);
 
-            Py::new(
-                py,
-                PyMftEntriesIterator {
-                    inner,
-                    total_number_of_records: n_records,
-                    current_record: 0,
-                    output_format,
-                    csv_header_written: false,
-                },
-            )
+        Ok(PyMftEntriesIterator {
+            inner,
+            paths: PathResolver::new(self.path_options),
+            total_number_of_records: n_records,
+            current_record: 0,
+            output_format,
+            strict,
+            include_empty,
+            csv_options: CsvOptions::default(),
+            csv_header_written: false,
+            json_options: JsonOptions::default(),
+            release_gil: self.release_gil,
         })
     }
+
+    /// Checks `threads` before the parser is consumed.
+    fn check_threads(&self, threads: usize) -> PyResult<()> {
+        if threads == 0 {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "threads must be positive",
+            ));
+        }
+
+        if threads > 1 && self.source.is_none() {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "threads cannot be used with a file-like object or a stream",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns an iterator with the same options as `iterator` for every thread,
+    /// each reading the MFT on its own. There are none if `threads` is 1.
+    fn workers(
+        &self,
+        iterator: &PyMftEntriesIterator,
+        threads: usize,
+    ) -> PyResult<Vec<PyMftEntriesIterator>> {
+        let source = match &self.source {
+            Some(source) if threads > 1 => source,
+            _ => return Ok(vec![]),
+        };
+
+        (0..threads)
+            .map(|_| {
+                let (read_seek, size) = source
+                    .try_clone()
+                    .expect("sources are kept only if they can be read again")
+                    .into_read_seek(self.file_options)?;
+                let reader = MftReader::from_read_seek(read_seek, size).map_err(PyMftError)?;
+
+                Ok(PyMftEntriesIterator {
+                    inner: reader,
+                    paths: PathResolver::new(self.path_options),
+                    total_number_of_records: iterator.total_number_of_records,
+                    current_record: iterator.current_record,
+                    output_format: iterator.output_format,
+                    strict: iterator.strict,
+                    include_empty: iterator.include_empty,
+                    csv_options: iterator.csv_options.clone(),
+                    csv_header_written: iterator.csv_header_written,
+                    json_options: iterator.json_options.clone(),
+                    release_gil: iterator.release_gil,
+                })
+            })
+            .collect()
+    }
+
+    fn flat_batch_reader(
+        &mut self,
+        strict: bool,
+        include_empty: bool,
+        batch_size: usize,
+    ) -> PyResult<FlatBatchReader> {
+        if batch_size == 0 {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "batch_size must be positive",
+            ));
+        }
+
+        // Batches hold the same columns as the CSV output.
+        let iterator = self.take_records_iterator(Output::CSV, strict, include_empty)?;
+
+        Ok(FlatBatchReader::new(iterator, batch_size))
+    }
+
+    fn records_iterator(
+        &mut self,
+        output_format: Output,
+        strict: bool,
+        include_empty: bool,
+    ) -> PyResult<Py<PyMftEntriesIterator>> {
+        let iterator = self.take_records_iterator(output_format, strict, include_empty)?;
+
+        Python::attach(|py| Py::new(py, iterator))
+    }
 }
 
 #[pyclass(unsendable)]
 pub struct PyMftEntriesIterator {
-    inner: MftParser<Box<dyn ReadSeek + Send>>,
+    inner: MftReader<Box<dyn ReadSeek + Send>>,
+    paths: PathResolver,
     total_number_of_records: u64,
     current_record: u64,
     output_format: Output,
+    strict: bool,
+    include_empty: bool,
+    csv_options: CsvOptions,
     csv_header_written: bool,
+    json_options: JsonOptions,
+    release_gil: bool,
 }
 
 #[pymethods]
@@ -171,105 +658,365 @@ impl PyMftEntriesIterator {
 }
 
 impl PyMftEntriesIterator {
-    fn entry_to_pyobject(
-        &mut self,
-        entry_result: Result<MftEntry, PyMftError>,
-        py: Python,
-    ) -> Py<PyAny> {
-        match entry_result {
-            Ok(entry) => match PyMftEntry::from_mft_entry(py, entry, &mut self.inner)
-                .and_then(|entry| entry.into_py_any(py))
-            {
-                Ok(py_mft_entry) => py_mft_entry,
-                Err(e) => e.into_py_any(py).unwrap(),
-            },
-            Err(e) => PyErr::from(e).into_py_any(py).unwrap(),
+    fn entry_to_pyobject(&mut self, record: Record, paths: EntryPaths, py: Python) -> Py<PyAny> {
+        // Entries are read from `entry_id * entry size`.
+        let offset = record.entry.header.record_number * record.entry.data.len() as u64;
+
+        match PyMftEntry::from_mft_entry(py, record, paths, Some(offset))
+            .and_then(|entry| entry.into_py_any(py))
+        {
+            Ok(py_mft_entry) => py_mft_entry,
+            Err(e) => e.into_py_any(py).unwrap(),
+        }
+    }
+
+    fn entry_to_json(&mut self, record: Record, paths: EntryPaths, py: Python) -> Py<PyAny> {
+        let mut json = Vec::new();
+
+        match self.json_options.write(&mut json, &record, paths) {
+            Ok(()) => PyString::new(py, &String::from_utf8_lossy(&json))
+                .into_any()
+                .unbind(),
+            Err(_e) => PyErr::new::<exceptions::PyRuntimeError, _>("JSON Serialization failed")
+                .into_py_any(py)
+                .unwrap(),
+        }
+    }
+
+    fn entry_to_dict(&mut self, record: Record, paths: EntryPaths, py: Python) -> Py<PyAny> {
+        match self.json_options.to_pyobject(py, &record, paths) {
+            Ok(dict) => dict,
+            Err(e) => e.into_py_any(py).unwrap(),
+        }
+    }
+
+    fn csv_row(&mut self, entry: &MftEntry) -> (FlatMftEntryWithName, FlatMftEntryPaths) {
+        let paths = self.paths.resolve(&mut self.inner, entry);
+
+        flat_row(entry, &paths)
+    }
+
+    fn entry_to_csv(&mut self, record: Record, paths: EntryPaths, py: Python) -> Py<PyAny> {
+        let mut writer = self.csv_options.writer(Vec::new());
+        let (entry, paths) = flat_row(&record.entry, &paths);
+
+        let result = if self.csv_header_written {
+            Ok(())
+        } else {
+            self.csv_header_written = true;
+            self.csv_options.write_header(&mut writer)
+        };
+
+        match result.and_then(|_| self.csv_options.write_row(&mut writer, &entry, &paths)) {
+            Ok(()) => {}
+            Err(_e) => {
+                return PyErr::new::<exceptions::PyRuntimeError, _>("CSV Serialization failed")
+                    .into_py_any(py)
+                    .unwrap()
+            }
+        }
+
+        match writer.into_inner() {
+            Ok(bytes) => PyBytes::new(py, &bytes).into_any().unbind(),
+            Err(e) => PyErr::new::<exceptions::PyRuntimeError, _>(e.to_string())
+                .into_py_any(py)
+                .unwrap(),
         }
     }
 
-    fn entry_to_json(
+    /// `None` if the entry has no timestamps to output.
+    fn entry_to_bodyfile(
         &mut self,
-        entry_result: Result<MftEntry, PyMftError>,
+        record: Record,
+        paths: EntryPaths,
         py: Python,
-    ) -> Py<PyAny> {
-        match entry_result {
-            Ok(entry) => match serde_json::to_string(&entry) {
-                Ok(s) => PyString::new(py, &s).into_any().unbind(),
-                Err(_e) => PyErr::new::<exceptions::PyRuntimeError, _>("JSON Serialization failed")
-                    .into_py_any(py)
-                    .unwrap(),
-            },
-            Err(e) => PyErr::from(e).into_py_any(py).unwrap(),
+    ) -> Option<Py<PyAny>> {
+        let lines = bodyfile_lines(&record.entry, &paths);
+
+        if lines.is_empty() {
+            return None;
         }
+
+        Some(PyString::new(py, &lines).into_any().unbind())
     }
 
-    fn entry_to_csv(
+    /// `None` if the entry has no timestamps to output.
+    fn entry_to_timeline(
         &mut self,
-        entry_result: Result<MftEntry, PyMftError>,
+        record: Record,
+        paths: EntryPaths,
         py: Python,
-    ) -> Py<PyAny> {
-        let mut writer = WriterBuilder::new()
-            .has_headers(!self.csv_header_written)
-            .from_writer(Vec::new());
+    ) -> Option<Py<PyAny>> {
+        let rows = timeline_rows(&record.entry, &paths);
 
-        if !self.csv_header_written {
-            self.csv_header_written = true
+        if rows.is_empty() {
+            return None;
         }
 
-        match entry_result {
-            Ok(entry) => {
-                match writer.serialize(FlatMftEntryWithName::from_entry(&entry, &mut self.inner)) {
-                    Ok(()) => {}
-                    Err(_e) => {
-                        return PyErr::new::<exceptions::PyRuntimeError, _>(
-                            "CSV Serialization failed",
-                        )
-                        .into_py_any(py)
-                        .unwrap()
-                    }
-                }
+        // The header is only written along with the first row.
+        let mut writer = WriterBuilder::new()
+            .has_headers(!self.csv_header_written)
+            .from_writer(Vec::new());
+        self.csv_header_written = true;
 
-                match writer.into_inner() {
-                    Ok(bytes) => PyBytes::new(py, &bytes).into_any().unbind(),
-                    Err(e) => PyErr::new::<exceptions::PyRuntimeError, _>(e.to_string())
+        for row in rows {
+            if writer.serialize(row).is_err() {
+                return Some(
+                    PyErr::new::<exceptions::PyRuntimeError, _>("CSV Serialization failed")
                         .into_py_any(py)
                         .unwrap(),
-                }
+                );
             }
-            Err(e) => PyErr::from(e).into_py_any(py).unwrap(),
         }
+
+        Some(match writer.into_inner() {
+            Ok(bytes) => PyBytes::new(py, &bytes).into_any().unbind(),
+            Err(e) => PyErr::new::<exceptions::PyRuntimeError, _>(e.to_string())
+                .into_py_any(py)
+                .unwrap(),
+        })
     }
 
-    fn next(&mut self) -> PyResult<Option<Py<PyAny>>> {
-        Python::attach(|py| loop {
+    /// The records left to read.
+    fn records(&self) -> Range<u64> {
+        self.current_record..self.total_number_of_records
+    }
+
+    /// Reads the next record to output.
+    /// Empty records are skipped unless `include_empty` is set,
+    /// and invalid records are errors if `strict` is set.
+    fn next_record(&mut self) -> Option<PyResult<Record>> {
+        loop {
             if self.current_record == self.total_number_of_records {
-                return Ok(None);
+                return None;
+            }
+
+            match self.inner.is_past_end(self.current_record) {
+                Ok(false) => {}
+                Ok(true) => return None,
+                Err(error) => {
+                    self.current_record += 1;
+                    return Some(Err(PyMftError(error).for_entry(self.current_record - 1)));
+                }
             }
 
-            let obj = match self.inner.get_entry(self.current_record) {
-                Ok(entry) => {
-                    if &entry.header.signature == ZERO_HEADER {
-                        self.current_record += 1;
+            let entry_id = self.current_record;
+            self.current_record += 1;
+
+            let record = match self.inner.read_record(entry_id) {
+                Ok(data) if data.starts_with(ZERO_HEADER) => {
+                    if !self.include_empty {
                         continue;
                     }
 
-                    let ret = match self.output_format {
-                        Output::Python => self.entry_to_pyobject(Ok(entry), py),
-                        Output::JSON => self.entry_to_json(Ok(entry), py),
-                        Output::CSV => self.entry_to_csv(Ok(entry), py),
-                    };
-
-                    Ok(Some(ret))
+                    Ok(Record::empty(data, entry_id))
                 }
-                Err(error) => Ok(Some(
-                    PyErr::from(PyMftError(error)).into_py_any(py).unwrap(),
+                Ok(data) => parse_record(data, entry_id),
+                Err(error) => Err(error),
+            };
+
+            return Some(match record {
+                Ok(record) if record.is_valid() || record.is_empty() || !self.strict => Ok(record),
+                Ok(record) => Err(with_entry_id(
+                    PyErr::new::<exceptions::PyRuntimeError, _>(record.invalid_reason()),
+                    entry_id,
                 )),
+                Err(error) => Err(PyMftError(error).for_entry(entry_id)),
+            });
+        }
+    }
+
+    /// Reads the next record to output, and resolves its paths.
+    fn next_resolved(&mut self) -> Option<PyResult<(Record, EntryPaths)>> {
+        let record = self.next_record()?;
+
+        Some(record.map(|record| {
+            let paths = self.paths.resolve(&mut self.inner, &record.entry);
+            (record, paths)
+        }))
+    }
+
+    fn next(&mut self) -> PyResult<Option<Py<PyAny>>> {
+        Python::attach(|py| loop {
+            // Only converting the entry needs the GIL (unless reading from a python file-like object).
+            let release_gil = self.release_gil;
+            let next = allow_threads(py, release_gil, || self.next_resolved());
+
+            let obj = match next {
+                None => return Ok(None),
+                Some(Ok((record, paths))) => match self.output_format {
+                    Output::Python => self.entry_to_pyobject(record, paths, py),
+                    Output::JSON => self.entry_to_json(record, paths, py),
+                    Output::Dict => self.entry_to_dict(record, paths, py),
+                    Output::CSV => self.entry_to_csv(record, paths, py),
+                    // Entries without timestamps are skipped, rather than yielded as empty lines.
+                    Output::Bodyfile => match self.entry_to_bodyfile(record, paths, py) {
+                        Some(obj) => obj,
+                        None => continue,
+                    },
+                    Output::Timeline => match self.entry_to_timeline(record, paths, py) {
+                        Some(obj) => obj,
+                        None => continue,
+                    },
+                },
+                Some(Err(error)) => error.into_py_any(py).unwrap(),
             };
 
-            self.current_record += 1;
-            return obj;
+            return Ok(Some(obj));
         })
     }
+
+    /// Writes all the remaining entries as CSV, returns the number of entries written.
+    fn write_csv(&mut self, writer: impl Write) -> PyResult<u64> {
+        let mut writer = self.csv_options.writer(writer);
+
+        self.csv_options
+            .write_header(&mut writer)
+            .map_err(|e| PyErr::new::<exceptions::PyIOError, _>(e.to_string()))?;
+        let written = self.write_csv_rows(&mut writer)?;
+
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Writes all the remaining entries as CSV rows (without a header),
+    /// returns the number of entries written.
+    fn write_csv_rows<W: Write>(&mut self, writer: &mut csv::Writer<W>) -> PyResult<u64> {
+        let mut written = 0;
+
+        while let Some(record) = self.next_record() {
+            let record = match record {
+                Ok(record) => record,
+                Err(error) => {
+                    warn!("skipping entry: {error}");
+                    continue;
+                }
+            };
+
+            let (entry, paths) = self.csv_row(&record.entry);
+
+            self.csv_options
+                .write_row(writer, &entry, &paths)
+                .map_err(|e| PyErr::new::<exceptions::PyIOError, _>(e.to_string()))?;
+            written += 1;
+        }
+
+        Ok(written)
+    }
+
+    /// Writes all the remaining entries as JSON lines, returns the number of entries written.
+    fn write_jsonl(&mut self, mut writer: impl Write) -> PyResult<u64> {
+        let mut written = 0;
+
+        while let Some(record) = self.next_record() {
+            let record = match record {
+                Ok(record) => record,
+                Err(error) => {
+                    warn!("skipping entry: {error}");
+                    continue;
+                }
+            };
+
+            let paths = self.paths.resolve(&mut self.inner, &record.entry);
+
+            self.json_options
+                .write(&mut writer, &record, paths)
+                .map_err(|e| PyErr::new::<exceptions::PyIOError, _>(e.to_string()))?;
+            writer.write_all(b"\n")?;
+            written += 1;
+        }
+
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+/// Runs `f` with the GIL released if `release_gil` is set,
+/// so that other python threads can run while it's reading from a file.
+fn allow_threads<T: Send>(py: Python, release_gil: bool, f: impl FnOnce() -> T + Send) -> T {
+    if release_gil {
+        py.detach(f)
+    } else {
+        f()
+    }
+}
+
+#[pyclass(unsendable)]
+pub struct PyMftEntriesBatchIterator {
+    inner: PyMftEntriesIterator,
+    batch_size: usize,
+}
+
+#[pymethods]
+impl PyMftEntriesBatchIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyMftEntriesBatchIterator>> {
+        Ok(slf.into())
+    }
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<Vec<Py<PyAny>>>> {
+        slf.next()
+    }
+}
+
+impl PyMftEntriesBatchIterator {
+    fn next(&mut self) -> PyResult<Option<Vec<Py<PyAny>>>> {
+        let mut batch = Vec::with_capacity(self.batch_size);
+
+        while batch.len() < self.batch_size {
+            match self.inner.next()? {
+                Some(obj) => batch.push(obj),
+                None => break,
+            }
+        }
+
+        if batch.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(batch))
+    }
+}
+
+/// parse_entry(data, /, entry_id=0)
+/// --
+///
+/// Parses a single raw MFT record (for example, one carved from a memory image).
+/// Since there is no MFT to look up parents in, paths only contain the file name.
+#[pyfunction]
+#[pyo3(signature = (data, /, entry_id=0))]
+fn parse_entry(py: Python, data: &[u8], entry_id: u64) -> PyResult<Py<PyMftEntry>> {
+    let record = parse_record(data.to_vec(), entry_id).map_err(PyMftError)?;
+    let paths = EntryPaths::detached(&record.entry);
+
+    PyMftEntry::from_mft_entry(py, record, paths, None)
+}
+
+/// carve_entries(path_or_file_like, /, alignment=512)
+/// --
+///
+/// Returns an iterator that yields the FILE (and BAAD) records found in an arbitrary blob,
+/// such as a memory image, a pagefile or unallocated clusters, as python objects.
+/// Records are searched for every `alignment` bytes, and only records with matching fixups are
+/// yielded. The offset of every record in the blob is available as `PyMftEntry.offset`.
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, /, alignment=512))]
+fn carve_entries(
+    py: Python,
+    path_or_file_like: Py<PyAny>,
+    alignment: u64,
+) -> PyResult<Py<PyMftCarvedEntriesIterator>> {
+    if alignment == 0 {
+        return Err(PyErr::new::<exceptions::PyValueError, _>(
+            "alignment must be greater than 0",
+        ));
+    }
+
+    let (boxed_read_seek, _size) =
+        FileOrFileLike::from_pyobject(path_or_file_like)?.into_read_seek(FileOptions::default())?;
+
+    Py::new(
+        py,
+        PyMftCarvedEntriesIterator::new(boxed_read_seek, alignment),
+    )
 }
 
 // Don't use double quotes ("") inside this docstring, this will crash pyo3.
@@ -279,10 +1026,17 @@ fn mft(m: &Bound<'_, PyModule>) -> PyResult<()> {
     init_logging(m.py()).ok();
 
     m.add_class::<PyMftParser>()?;
+    m.add_function(wrap_pyfunction!(parse_entry, m)?)?;
+    m.add_function(wrap_pyfunction!(carve_entries, m)?)?;
 
     // Entry
     m.add_class::<PyMftEntriesIterator>()?;
+    m.add_class::<PyMftEntriesBatchIterator>()?;
+    m.add_class::<PyMftCarvedEntriesIterator>()?;
     m.add_class::<PyMftEntry>()?;
+    m.add_class::<PyMftEntryPath>()?;
+    m.add_class::<PyMftFixupError>()?;
+    m.add_class::<PyMftTimestampAnomaly>()?;
 
     // Attributes
     m.add_class::<PyMftAttribute>()?;
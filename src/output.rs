@@ -0,0 +1,668 @@
+use crate::csv_row::row_fields;
+use crate::path::{EntryPaths, PyMftEntryPath};
+use crate::reader::{PyMftFixupError, Record};
+use crate::serializer::PySerializer;
+
+use csv::{QuoteStyle, WriterBuilder};
+use mft_rs::attribute::header::ResidentialHeader;
+use mft_rs::attribute::x30::FileNamespace;
+use mft_rs::attribute::MftAttributeType;
+use mft_rs::csv::FlatMftEntryWithName;
+use mft_rs::entry::EntryFlags;
+use mft_rs::{MftAttribute, MftEntry, Timestamp};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::ser::Error as _;
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use std::fmt::{self, Write as _};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Used for JSON output, adds the resolved paths and validity of the record to the serialized entry.
+#[derive(Serialize)]
+pub struct JsonMftEntry<'a> {
+    #[serde(flatten)]
+    entry: &'a MftEntry,
+    /// `None` if the entry has nothing to resolve a path from.
+    full_path: Option<String>,
+    path_status: String,
+    path_error: Option<String>,
+    full_paths: Vec<PyMftEntryPath>,
+    valid: bool,
+    fixup_errors: &'a [PyMftFixupError],
+    empty: bool,
+}
+
+impl<'a> JsonMftEntry<'a> {
+    pub fn new(record: &'a Record, paths: EntryPaths) -> Self {
+        JsonMftEntry {
+            entry: &record.entry,
+            full_path: paths
+                .full_path
+                .as_ref()
+                .map(|p| p.path.to_string_lossy().to_string()),
+            path_status: paths.status().to_string(),
+            path_error: paths.error,
+            full_paths: paths.full_paths,
+            valid: record.is_valid(),
+            fixup_errors: &record.fixup_errors,
+            empty: record.is_empty(),
+        }
+    }
+}
+
+/// The top level fields of the JSON output.
+pub const JSON_FIELDS: [&str; 10] = [
+    "header",
+    "attributes",
+    "valid_fixup",
+    "full_path",
+    "path_status",
+    "path_error",
+    "full_paths",
+    "valid",
+    "fixup_errors",
+    "empty",
+];
+
+/// Options for JSON output.
+#[derive(Debug, Clone, Default)]
+pub struct JsonOptions {
+    pretty: bool,
+    /// Output the columns of the CSV output instead of the nested entry.
+    flat: bool,
+    /// The fields to output, in order (all of them if `None`).
+    fields: Option<Vec<String>>,
+}
+
+impl JsonOptions {
+    /// Validates the options given to `entries_json` and `to_jsonl`.
+    pub fn new(pretty: bool, flat: bool, fields: Option<Vec<String>>) -> PyResult<Self> {
+        let known_fields: &[&str] = if flat { &CSV_COLUMNS } else { &JSON_FIELDS };
+
+        for field in fields.iter().flatten() {
+            if !known_fields.contains(&field.as_str()) {
+                return Err(PyValueError::new_err(format!(
+                    "unknown field `{field}`, expected one of {known_fields:?}"
+                )));
+            }
+        }
+
+        Ok(JsonOptions {
+            pretty,
+            flat,
+            fields,
+        })
+    }
+
+    /// Writes an entry as a single JSON value (spanning multiple lines if `pretty` is set).
+    pub fn write<W: Write>(
+        &self,
+        writer: W,
+        record: &Record,
+        paths: EntryPaths,
+    ) -> serde_json::Result<()> {
+        if self.pretty {
+            self.serialize(&mut serde_json::Serializer::pretty(writer), record, paths)
+        } else {
+            self.serialize(&mut serde_json::Serializer::new(writer), record, paths)
+        }
+    }
+
+    /// Converts an entry to python objects, the same as `json.loads` of its JSON.
+    pub fn to_pyobject(
+        &self,
+        py: Python,
+        record: &Record,
+        paths: EntryPaths,
+    ) -> PyResult<Py<PyAny>> {
+        Ok(self
+            .serialize(PySerializer::new(py), record, paths)?
+            .unbind())
+    }
+
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+        record: &Record,
+        paths: EntryPaths,
+    ) -> Result<S::Ok, S::Error> {
+        if !self.flat && self.fields.is_none() {
+            return JsonMftEntry::new(record, paths).serialize(serializer);
+        }
+
+        let mut values = if self.flat {
+            let (entry, paths) = flat_row(&record.entry, &paths);
+            flat_values(&entry, &paths)
+        } else {
+            match serde_json::to_value(JsonMftEntry::new(record, paths)) {
+                Ok(Value::Object(values)) => values,
+                Ok(_) => unreachable!("entries are structs"),
+                Err(e) => return Err(S::Error::custom(e)),
+            }
+        };
+
+        let fields = match &self.fields {
+            Some(fields) => fields
+                .iter()
+                .filter_map(|field| values.remove(field).map(|value| (field.clone(), value)))
+                .collect(),
+            // Only flat entries get here without fields.
+            None => CSV_COLUMNS
+                .iter()
+                .filter_map(|column| {
+                    values
+                        .remove(*column)
+                        .map(|value| (column.to_string(), value))
+                })
+                .collect(),
+        };
+
+        OrderedFields(fields).serialize(serializer)
+    }
+}
+
+/// Serializes as a map, keeping the order of the fields (unlike `serde_json::Map`, which sorts them).
+struct OrderedFields(Vec<(String, Value)>);
+
+impl Serialize for OrderedFields {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(field, value)| (field, value)))
+    }
+}
+
+/// Used for CSV output, columns appended after those of `FlatMftEntryWithName`.
+/// `FullPaths` and `FullPathNamespaces` hold one value per $FILE_NAME attribute, separated by `|`.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FlatMftEntryPaths {
+    pub path_status: String,
+    pub path_error: String,
+    pub full_paths: String,
+    pub full_path_namespaces: String,
+}
+
+impl FlatMftEntryPaths {
+    pub fn from_paths(paths: &EntryPaths) -> Self {
+        FlatMftEntryPaths {
+            path_status: paths.status().to_string(),
+            path_error: paths.error.clone().unwrap_or_default(),
+            full_paths: paths
+                .full_paths
+                .iter()
+                .map(|p| p.path.as_str())
+                .collect::<Vec<_>>()
+                .join("|"),
+            full_path_namespaces: paths
+                .full_paths
+                .iter()
+                .map(|p| p.namespace.as_str())
+                .collect::<Vec<_>>()
+                .join("|"),
+        }
+    }
+}
+
+/// The columns of the CSV output, in their default order.
+pub const CSV_COLUMNS: [&str; 26] = [
+    "Signature",
+    "EntryId",
+    "Sequence",
+    "BaseEntryId",
+    "BaseEntrySequence",
+    "HardLinkCount",
+    "Flags",
+    "UsedEntrySize",
+    "TotalEntrySize",
+    "FileSize",
+    "IsADirectory",
+    "IsDeleted",
+    "HasAlternateDataStreams",
+    "StandardInfoFlags",
+    "StandardInfoLastModified",
+    "StandardInfoLastAccess",
+    "StandardInfoCreated",
+    "FileNameFlags",
+    "FileNameLastModified",
+    "FileNameLastAccess",
+    "FileNameCreated",
+    "FullPath",
+    "PathStatus",
+    "PathError",
+    "FullPaths",
+    "FullPathNamespaces",
+];
+
+/// The indices of the timestamp columns in `CSV_COLUMNS`, in the order of the fields of
+/// `FlatMftEntryWithName`.
+const TIMESTAMP_COLUMNS: [usize; 6] = [14, 15, 16, 18, 19, 20];
+
+/// Options for CSV output.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// The columns to output, in order.
+    columns: Vec<&'static str>,
+    /// The indices of `columns` in `CSV_COLUMNS`.
+    column_indices: Vec<usize>,
+    delimiter: u8,
+    quote_style: QuoteStyle,
+    /// A `strftime` format for timestamps (RFC 3339 if `None`).
+    timestamp_format: Option<String>,
+    header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            columns: CSV_COLUMNS.to_vec(),
+            column_indices: (0..CSV_COLUMNS.len()).collect(),
+            delimiter: b',',
+            quote_style: QuoteStyle::Necessary,
+            timestamp_format: None,
+            header: true,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Validates the options given to `entries_csv` and `to_csv`.
+    pub fn new(
+        columns: Option<Vec<String>>,
+        delimiter: &str,
+        quoting: &str,
+        timestamp_format: Option<String>,
+        header: bool,
+    ) -> PyResult<Self> {
+        let column_indices: Vec<usize> = match columns {
+            Some(columns) => columns
+                .iter()
+                .map(|column| {
+                    CSV_COLUMNS
+                        .iter()
+                        .position(|c| *c == column)
+                        .ok_or_else(|| {
+                            PyValueError::new_err(format!(
+                                "unknown column `{column}`, expected one of {CSV_COLUMNS:?}"
+                            ))
+                        })
+                })
+                .collect::<PyResult<_>>()?,
+            None => (0..CSV_COLUMNS.len()).collect(),
+        };
+        let columns = column_indices.iter().map(|i| CSV_COLUMNS[*i]).collect();
+
+        let delimiter = match delimiter.as_bytes() {
+            [delimiter] => *delimiter,
+            _ => {
+                return Err(PyValueError::new_err(
+                    "delimiter must be a single ASCII character",
+                ))
+            }
+        };
+
+        // Named after the quoting constants of python's `csv` module.
+        let quote_style = match quoting {
+            "minimal" => QuoteStyle::Necessary,
+            "all" => QuoteStyle::Always,
+            "nonnumeric" => QuoteStyle::NonNumeric,
+            "none" => QuoteStyle::Never,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown quoting `{quoting}`, expected one of `minimal`, `all`, `nonnumeric` or `none`"
+                )))
+            }
+        };
+
+        if let Some(format) = &timestamp_format {
+            format_timestamp(&Timestamp::UNIX_EPOCH, format).map_err(|_| {
+                PyValueError::new_err(format!("invalid timestamp format `{format}`"))
+            })?;
+        }
+
+        Ok(CsvOptions {
+            columns,
+            column_indices,
+            delimiter,
+            quote_style,
+            timestamp_format,
+            header,
+        })
+    }
+
+    pub fn writer<W: Write>(&self, writer: W) -> csv::Writer<W> {
+        // The header is written by `write_header`, since rows are written as plain records.
+        WriterBuilder::new()
+            .has_headers(false)
+            .delimiter(self.delimiter)
+            .quote_style(self.quote_style)
+            .from_writer(writer)
+    }
+
+    pub fn write_header<W: Write>(&self, writer: &mut csv::Writer<W>) -> csv::Result<()> {
+        if self.header {
+            writer.write_record(&self.columns)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the selected columns of an entry as a row.
+    pub fn write_row<W: Write>(
+        &self,
+        writer: &mut csv::Writer<W>,
+        entry: &FlatMftEntryWithName,
+        paths: &FlatMftEntryPaths,
+    ) -> csv::Result<()> {
+        let all_columns = self.column_indices.len() == CSV_COLUMNS.len()
+            && self.column_indices.iter().copied().eq(0..CSV_COLUMNS.len());
+
+        if all_columns && self.timestamp_format.is_none() {
+            return writer.serialize((entry, paths));
+        }
+
+        let mut fields = row_fields(&(entry, paths))?;
+
+        if let Some(format) = &self.timestamp_format {
+            let timestamps = [
+                entry.standard_info_last_modified,
+                entry.standard_info_last_access,
+                entry.standard_info_created,
+                entry.file_name_last_modified,
+                entry.file_name_last_access,
+                entry.file_name_created,
+            ];
+
+            for (index, timestamp) in TIMESTAMP_COLUMNS.iter().zip(timestamps) {
+                if let Some(timestamp) = timestamp {
+                    fields[*index] = format_timestamp(&timestamp, format).map_err(|_| {
+                        csv::Error::custom(format!(
+                            "failed to format timestamp {timestamp} with `{format}`"
+                        ))
+                    })?;
+                }
+            }
+        }
+
+        writer.write_record(self.column_indices.iter().map(|i| &fields[*i]))
+    }
+}
+
+/// Returns the CSV columns of an entry, by name.
+fn flat_values(entry: &FlatMftEntryWithName, paths: &FlatMftEntryPaths) -> Map<String, Value> {
+    match (serde_json::to_value(entry), serde_json::to_value(paths)) {
+        (Ok(Value::Object(mut entry)), Ok(Value::Object(paths))) => {
+            entry.extend(paths);
+            entry
+        }
+        _ => unreachable!("both are structs"),
+    }
+}
+
+fn format_timestamp(timestamp: &Timestamp, format: &str) -> Result<String, fmt::Error> {
+    let mut formatted = String::new();
+    write!(formatted, "{}", timestamp.strftime(format))?;
+
+    Ok(formatted)
+}
+
+/// Returns the values of the CSV columns of an entry, with its resolved path.
+pub fn flat_row(entry: &MftEntry, paths: &EntryPaths) -> (FlatMftEntryWithName, FlatMftEntryPaths) {
+    let full_path = paths
+        .full_path
+        .as_ref()
+        .map(|p| p.path.clone())
+        .unwrap_or_default();
+
+    (
+        flat_entry_with_name(entry, full_path),
+        FlatMftEntryPaths::from_paths(paths),
+    )
+}
+
+/// Same as `FlatMftEntryWithName::from_entry`, but takes an already resolved path,
+/// since `from_entry` panics if resolving the path fails.
+pub fn flat_entry_with_name(entry: &MftEntry, full_path: PathBuf) -> FlatMftEntryWithName {
+    let entry_attributes: Vec<MftAttribute> = entry
+        .iter_attributes_matching(Some(vec![
+            MftAttributeType::FileName,
+            MftAttributeType::StandardInformation,
+            MftAttributeType::DATA,
+        ]))
+        .filter_map(Result::ok)
+        .collect();
+
+    let file_name = entry_attributes
+        .iter()
+        .find(|a| a.header.type_code == MftAttributeType::FileName)
+        .and_then(|a| a.data.clone().into_file_name());
+
+    let standard_info = entry_attributes
+        .iter()
+        .find(|a| a.header.type_code == MftAttributeType::StandardInformation)
+        .and_then(|a| a.data.clone().into_standard_info());
+
+    let has_ads = entry_attributes
+        .iter()
+        .any(|a| a.header.type_code == MftAttributeType::DATA && !a.header.name.is_empty());
+
+    FlatMftEntryWithName {
+        entry_id: entry.header.record_number,
+        // Empty records have a zeroed signature.
+        signature: String::from_utf8_lossy(&entry.header.signature.to_ascii_uppercase())
+            .trim_end_matches('\0')
+            .to_string(),
+        sequence: entry.header.sequence,
+        hard_link_count: entry.header.hard_link_count,
+        flags: entry.header.flags.clone(),
+        used_entry_size: entry.header.used_entry_size,
+        total_entry_size: entry.header.total_entry_size,
+        base_entry_id: entry.header.base_reference.entry,
+        base_entry_sequence: entry.header.base_reference.sequence,
+        is_a_directory: entry.is_dir(),
+        is_deleted: !entry.header.flags.contains(EntryFlags::ALLOCATED),
+        has_alternate_data_streams: has_ads,
+        standard_info_flags: standard_info.as_ref().map(|i| i.file_flags.clone()),
+        standard_info_last_modified: standard_info.as_ref().map(|i| i.modified),
+        standard_info_last_access: standard_info.as_ref().map(|i| i.accessed),
+        standard_info_created: standard_info.as_ref().map(|i| i.created),
+        file_name_flags: file_name.as_ref().map(|i| i.flags.clone()),
+        file_name_last_modified: file_name.as_ref().map(|i| i.modified),
+        file_name_last_access: file_name.as_ref().map(|i| i.accessed),
+        file_name_created: file_name.as_ref().map(|i| i.created),
+        file_size: data_size(entry),
+        full_path,
+    }
+}
+
+/// The timestamps of an entry found in one attribute.
+struct TimestampSource {
+    path: String,
+    /// `None` for $STANDARD_INFORMATION.
+    namespace: Option<FileNamespace>,
+    modified: Timestamp,
+    accessed: Timestamp,
+    mft_modified: Timestamp,
+    created: Timestamp,
+}
+
+/// Returns the $STANDARD_INFORMATION timestamps of an entry (if it has one), followed by those
+/// of every $FILE_NAME attribute.
+///
+/// Timestamps are never dropped for lack of a path: a $FILE_NAME attribute without a resolved
+/// path is named after the attribute alone, and so is $STANDARD_INFORMATION (after the best
+/// $FILE_NAME, or with an empty name if there is none).
+fn timestamp_sources(entry: &MftEntry, paths: &EntryPaths) -> Vec<TimestampSource> {
+    let attributes: Vec<MftAttribute> = entry
+        .iter_attributes_matching(Some(vec![
+            MftAttributeType::StandardInformation,
+            MftAttributeType::FileName,
+        ]))
+        .filter_map(Result::ok)
+        .collect();
+
+    let standard_info = attributes
+        .iter()
+        .find_map(|a| a.data.clone().into_standard_info());
+
+    let mut sources = vec![];
+
+    if let Some(standard_info) = standard_info {
+        let path = match &paths.full_path {
+            Some(full_path) => full_path.path.to_string_lossy().to_string(),
+            None => entry
+                .find_best_name_attribute()
+                .map(|file_name| file_name.name)
+                .unwrap_or_default(),
+        };
+
+        sources.push(TimestampSource {
+            path,
+            namespace: None,
+            modified: standard_info.modified,
+            accessed: standard_info.accessed,
+            mft_modified: standard_info.mft_modified,
+            created: standard_info.created,
+        });
+    }
+
+    let file_names = attributes
+        .iter()
+        .filter_map(|a| a.data.clone().into_file_name());
+
+    // `full_paths` has a path for every $FILE_NAME attribute, in order (unless resolving failed).
+    for (i, file_name) in file_names.enumerate() {
+        sources.push(TimestampSource {
+            path: paths
+                .full_paths
+                .get(i)
+                .map_or_else(|| file_name.name.clone(), |path| path.path.clone()),
+            namespace: Some(file_name.namespace),
+            modified: file_name.modified,
+            accessed: file_name.accessed,
+            mft_modified: file_name.mft_modified,
+            created: file_name.created,
+        });
+    }
+
+    sources
+}
+
+/// The size of the first $DATA attribute of an entry (0 if it has none).
+pub(crate) fn data_size(entry: &MftEntry) -> u64 {
+    entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
+        .find_map(Result::ok)
+        .map_or(0, |attr| match &attr.header.residential_header {
+            ResidentialHeader::Resident(r) => u64::from(r.data_size),
+            ResidentialHeader::NonResident(nr) => nr.file_size,
+        })
+}
+
+/// Formats the timestamps of an entry as Sleuth Kit bodyfile (mactime) lines:
+/// `MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime`.
+///
+/// There is a line for the $STANDARD_INFORMATION timestamps, and one for the timestamps of every
+/// $FILE_NAME attribute (except DOS names, which have the same timestamps as their long name),
+/// with ` ($FILE_NAME)` appended to the name.
+pub fn bodyfile_lines(entry: &MftEntry, paths: &EntryPaths) -> String {
+    let inode = format!("{}-{}", entry.header.record_number, entry.header.sequence);
+    let mode = if entry.is_dir() {
+        "d/drwxrwxrwx"
+    } else {
+        "r/rrwxrwxrwx"
+    };
+    let deleted = if entry.is_allocated() {
+        ""
+    } else {
+        " (deleted)"
+    };
+    let size = data_size(entry);
+
+    let mut lines = String::new();
+
+    for source in timestamp_sources(entry, paths) {
+        let name = match source.namespace {
+            None => source.path,
+            Some(FileNamespace::DOS) => continue,
+            Some(_) => format!("{} ($FILE_NAME)", source.path),
+        };
+
+        lines.push_str(&format!(
+            "0|{name}{deleted}|{inode}|{mode}|0|0|{size}|{}|{}|{}|{}\n",
+            bodyfile_time(&source.accessed),
+            bodyfile_time(&source.modified),
+            bodyfile_time(&source.mft_modified),
+            bodyfile_time(&source.created),
+        ));
+    }
+
+    lines
+}
+
+/// What an unset (zero) FILETIME converts to: 1601-01-01, in seconds since the Unix epoch.
+const FILETIME_EPOCH: i64 = -11_644_473_600;
+
+/// Seconds since the Unix epoch, or 0 for an unset timestamp (like The Sleuth Kit).
+fn bodyfile_time(timestamp: &Timestamp) -> i64 {
+    match timestamp.as_second() {
+        FILETIME_EPOCH => 0,
+        seconds => seconds,
+    }
+}
+
+/// Used for timeline output, a row for every timestamp of an entry.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TimelineRow {
+    timestamp: Timestamp,
+    /// Which timestamp this is, in MACB notation (`M...`, `.A..`, `..C.` or `...B`).
+    #[serde(rename = "MACB")]
+    macb: &'static str,
+    /// `SI` ($STANDARD_INFORMATION) or `FN` ($FILE_NAME).
+    source: &'static str,
+    /// The $FILE_NAME namespace, empty for `SI`.
+    file_name_namespace: String,
+    entry_id: u64,
+    sequence: u16,
+    is_deleted: bool,
+    full_path: String,
+}
+
+/// Returns a row for each of the four timestamps of every attribute with timestamps.
+pub fn timeline_rows(entry: &MftEntry, paths: &EntryPaths) -> Vec<TimelineRow> {
+    let mut rows = vec![];
+
+    for source in timestamp_sources(entry, paths) {
+        let timestamps = [
+            ("M...", source.modified),
+            (".A..", source.accessed),
+            ("..C.", source.mft_modified),
+            ("...B", source.created),
+        ];
+
+        for (macb, timestamp) in timestamps {
+            rows.push(TimelineRow {
+                timestamp,
+                macb,
+                source: if source.namespace.is_some() {
+                    "FN"
+                } else {
+                    "SI"
+                },
+                file_name_namespace: source
+                    .namespace
+                    .as_ref()
+                    .map(|n| format!("{n:?}"))
+                    .unwrap_or_default(),
+                entry_id: entry.header.record_number,
+                sequence: entry.header.sequence,
+                is_deleted: !entry.is_allocated(),
+                full_path: source.path.clone(),
+            });
+        }
+    }
+
+    rows
+}
@@ -0,0 +1,135 @@
+use crate::PyMftEntriesIterator;
+
+use pyo3::PyResult;
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
+
+/// Number of records a worker formats at a time.
+const CHUNK_SIZE: u64 = 4096;
+
+/// The chunks workers may claim, relative to the next chunk to write.
+///
+/// Finished chunks are kept in memory until it's their turn to be written,
+/// so workers wait instead of getting too far ahead of a slow chunk (or writer).
+struct Window {
+    /// The next chunk to write, `None` once writing stopped.
+    next_to_write: Mutex<Option<u64>>,
+    moved: Condvar,
+    size: u64,
+}
+
+impl Window {
+    fn new(size: u64) -> Self {
+        Window {
+            next_to_write: Mutex::new(Some(0)),
+            moved: Condvar::new(),
+            size,
+        }
+    }
+
+    /// Waits until `chunk` is in the window. Returns `false` if writing stopped.
+    fn wait_for(&self, chunk: u64) -> bool {
+        let next_to_write = self.next_to_write.lock().unwrap();
+        let next_to_write = self
+            .moved
+            .wait_while(next_to_write, |next| {
+                next.is_some_and(|next| chunk >= next + self.size)
+            })
+            .unwrap();
+
+        next_to_write.is_some()
+    }
+
+    fn move_to(&self, next_to_write: Option<u64>) {
+        *self.next_to_write.lock().unwrap() = next_to_write;
+        self.moved.notify_all();
+    }
+}
+
+/// Formats `records` on a thread per worker, and writes them to `writer` in entry order.
+/// Returns the number of entries written.
+///
+/// Every worker reads the MFT through its own reader (and resolves paths with its own cache),
+/// claims chunks of `CHUNK_SIZE` records (at most `2 * workers` chunks past the next chunk to
+/// write), and formats them with `write_chunk`, which returns the number of entries it wrote.
+pub fn write_parallel<W, F>(
+    records: Range<u64>,
+    workers: Vec<PyMftEntriesIterator>,
+    mut writer: W,
+    write_chunk: F,
+) -> PyResult<u64>
+where
+    W: Write,
+    F: Fn(&mut PyMftEntriesIterator, &mut Vec<u8>) -> PyResult<u64> + Sync,
+{
+    let chunks = (records.end - records.start).div_ceil(CHUNK_SIZE);
+    let next_chunk = AtomicU64::new(0);
+    let window = Window::new(workers.len() as u64 * 2);
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+
+        for mut worker in workers {
+            let sender = sender.clone();
+            let records = records.clone();
+            let next_chunk = &next_chunk;
+            let window = &window;
+            let write_chunk = &write_chunk;
+
+            scope.spawn(move || loop {
+                let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+                if chunk >= chunks || !window.wait_for(chunk) {
+                    break;
+                }
+
+                let start = records.start + chunk * CHUNK_SIZE;
+                worker.current_record = start;
+                worker.total_number_of_records = (start + CHUNK_SIZE).min(records.end);
+
+                let mut output = Vec::new();
+                let result = write_chunk(&mut worker, &mut output).map(|written| (output, written));
+
+                // The receiver is gone if writing failed, there is no point in going on.
+                if sender.send((chunk, result)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        drop(sender);
+
+        let mut write_chunks = || -> PyResult<u64> {
+            // Chunks are finished out of order, so they are kept until it's their turn.
+            let mut pending = BTreeMap::new();
+            let mut next_to_write = 0;
+            let mut written = 0;
+
+            for (chunk, result) in &receiver {
+                pending.insert(chunk, result);
+
+                while let Some(result) = pending.remove(&next_to_write) {
+                    let (output, chunk_written) = result?;
+
+                    writer.write_all(&output)?;
+                    written += chunk_written;
+                    next_to_write += 1;
+                    window.move_to(Some(next_to_write));
+                }
+            }
+
+            writer.flush()?;
+            Ok(written)
+        };
+
+        let result = write_chunks();
+        // Wakes up the workers waiting for the window, so they stop if writing failed.
+        window.move_to(None);
+
+        result
+    })
+}
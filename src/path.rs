@@ -0,0 +1,401 @@
+use crate::reader::MftReader;
+use crate::ReadSeek;
+
+use lru::LruCache;
+use mft_rs::attribute::MftAttributeType;
+use mft_rs::err::{Error, Result};
+use mft_rs::{FileNameAttr, MftEntry};
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+/// MFT entry 5 is the root directory.
+const ROOT_ENTRY_ID: u64 = 5;
+
+/// Parent chains deeper than this are considered corrupted.
+/// NTFS paths are limited to 32,767 characters, so real chains are much shallower.
+const MAX_PATH_DEPTH: usize = 256;
+
+/// The virtual directory other forensic tools (e.g. The Sleuth Kit) place orphans under.
+const ORPHAN_FILES: &str = "$OrphanFiles";
+/// The placeholder root of paths whose parent cannot be resolved, whatever the reason
+/// (which is in their `PathStatus`).
+const UNKNOWN_ROOT: &str = "[Unknown]";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PathOptions {
+    /// Treat a parent reference whose sequence number doesn't match the parent entry
+    /// as stale (the parent entry was reused by another file).
+    pub verify_parent_sequence: bool,
+    /// Place entries whose parent cannot be resolved under `$OrphanFiles`,
+    /// instead of the `[Unknown]` placeholder.
+    pub mark_orphans: bool,
+}
+
+/// Describes how a full path was resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathStatus {
+    /// The whole parent chain was resolved up to the root directory.
+    Resolved,
+    /// A parent in the chain is missing, unreadable or not a directory.
+    Orphan,
+    /// A parent in the chain was reused (only detected with `verify_parent_sequence`).
+    SequenceMismatch,
+    /// The parent chain references itself.
+    Loop,
+    /// The entry has no $FILE_NAME attribute (nor a base entry) to resolve from.
+    Unknown,
+    /// Resolution failed, for example because of an I/O error while reading a parent.
+    Error,
+}
+
+impl fmt::Display for PathStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PathStatus::Resolved => "resolved",
+            PathStatus::Orphan => "orphan",
+            PathStatus::SequenceMismatch => "sequence-mismatch",
+            PathStatus::Loop => "loop",
+            PathStatus::Unknown => "unknown",
+            PathStatus::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedPath {
+    pub path: PathBuf,
+    pub status: PathStatus,
+}
+
+impl ResolvedPath {
+    fn new(path: PathBuf, status: PathStatus) -> Self {
+        ResolvedPath { path, status }
+    }
+}
+
+/// All the paths of an entry.
+/// Resolution errors are recorded here instead of failing the whole entry.
+#[derive(Debug, Clone)]
+pub struct EntryPaths {
+    /// `None` if the entry has nothing to resolve a path from.
+    pub full_path: Option<ResolvedPath>,
+    pub full_paths: Vec<PyMftEntryPath>,
+    pub error: Option<String>,
+}
+
+impl EntryPaths {
+    /// Paths of an entry parsed on its own, without an MFT to look up its parents in.
+    /// Only the file names are known, so entries not directly under the root are orphans.
+    pub fn detached(entry: &MftEntry) -> Self {
+        let path_for_file_name = |file_name: &FileNameAttr| {
+            let status = if file_name.parent.entry == ROOT_ENTRY_ID {
+                PathStatus::Resolved
+            } else {
+                PathStatus::Orphan
+            };
+
+            ResolvedPath::new(PathBuf::from(&file_name.name), status)
+        };
+
+        EntryPaths {
+            full_path: entry
+                .find_best_name_attribute()
+                .map(|file_name| path_for_file_name(&file_name)),
+            full_paths: file_names(entry)
+                .iter()
+                .map(|file_name| PyMftEntryPath::new(file_name, path_for_file_name(file_name)))
+                .collect(),
+            error: None,
+        }
+    }
+
+    pub fn status(&self) -> PathStatus {
+        match (&self.error, &self.full_path) {
+            (Some(_), _) => PathStatus::Error,
+            (None, Some(resolved)) => resolved.status,
+            (None, None) => PathStatus::Unknown,
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Serialize, Debug, Clone)]
+/// A full path of an entry, as resolved from one of its $FILE_NAME attributes.
+pub struct PyMftEntryPath {
+    #[pyo3(get)]
+    pub path: String,
+    /// The $FILE_NAME namespace (`Win32`, `DOS`, `Win32AndDos` or `POSIX`).
+    #[pyo3(get)]
+    pub namespace: String,
+    #[pyo3(get)]
+    pub parent_entry_id: u64,
+    #[pyo3(get)]
+    pub parent_entry_sequence: u16,
+    /// One of `resolved`, `orphan`, `sequence-mismatch` or `loop`.
+    #[pyo3(get)]
+    pub status: String,
+}
+
+impl PyMftEntryPath {
+    fn new(file_name: &FileNameAttr, resolved: ResolvedPath) -> Self {
+        PyMftEntryPath {
+            path: resolved.path.to_string_lossy().to_string(),
+            namespace: format!("{:?}", file_name.namespace),
+            parent_entry_id: file_name.parent.entry,
+            parent_entry_sequence: file_name.parent.sequence,
+            status: resolved.status.to_string(),
+        }
+    }
+}
+
+fn file_names(entry: &MftEntry) -> Vec<FileNameAttr> {
+    entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::FileName]))
+        .filter_map(Result::ok)
+        .filter_map(|a| a.data.into_file_name())
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct Directory {
+    /// `None` if the directory entry could not be read.
+    sequence: Option<u16>,
+    allocated: bool,
+    resolved: ResolvedPath,
+}
+
+impl Directory {
+    fn is_referenced_by(&self, reference_sequence: u16) -> bool {
+        // A sequence number of 0 means the reference should not be checked.
+        if reference_sequence == 0 {
+            return true;
+        }
+
+        match self.sequence {
+            Some(sequence) if sequence == reference_sequence => true,
+            // The sequence number is incremented when an entry is freed,
+            // so children of a deleted directory are one behind it.
+            Some(sequence) => !self.allocated && sequence == reference_sequence.wrapping_add(1),
+            None => false,
+        }
+    }
+}
+
+/// Resolves full paths of entries by walking their parent references.
+/// Paths of parent directories are cached, since they are shared by many entries.
+pub struct PathResolver {
+    options: PathOptions,
+    directories: LruCache<u64, Directory>,
+    // Directories currently being resolved, used to detect loops in the parent chain.
+    visiting: Vec<u64>,
+}
+
+impl PathResolver {
+    pub fn new(options: PathOptions) -> Self {
+        PathResolver {
+            options,
+            directories: LruCache::new(NonZeroUsize::new(1000).expect("1000 > 0")),
+            visiting: Vec::new(),
+        }
+    }
+
+    /// Resolves both the best path and all the paths of an entry.
+    /// Never fails, errors are recorded in `EntryPaths::error`.
+    pub fn resolve(
+        &mut self,
+        parser: &mut MftReader<impl ReadSeek>,
+        entry: &MftEntry,
+    ) -> EntryPaths {
+        let resolved = self.full_path(parser, entry).and_then(|full_path| {
+            let full_paths = self.full_paths(parser, entry)?;
+            Ok((full_path, full_paths))
+        });
+
+        match resolved {
+            Ok((full_path, full_paths)) => EntryPaths {
+                full_path,
+                full_paths,
+                error: None,
+            },
+            Err(e) => EntryPaths {
+                full_path: None,
+                full_paths: vec![],
+                error: Some(error_chain(&e)),
+            },
+        }
+    }
+
+    /// Gets the most human-readable full path for an entry
+    /// (prefers `Win32` names, and falls back to `DOS` names).
+    fn full_path(
+        &mut self,
+        parser: &mut MftReader<impl ReadSeek>,
+        entry: &MftEntry,
+    ) -> Result<Option<ResolvedPath>> {
+        match entry.find_best_name_attribute() {
+            Some(file_name) => Ok(Some(self.path_for_file_name(parser, entry, &file_name)?)),
+            None => match entry.header.base_reference.entry {
+                // I don't have a parent reference, and no X30 attribute.
+                0 => Ok(None),
+                base_entry_id => Ok(Some(self.directory_path(
+                    parser,
+                    base_entry_id,
+                    entry.header.base_reference.sequence,
+                )?)),
+            },
+        }
+    }
+
+    /// Gets a full path for every $FILE_NAME attribute of an entry,
+    /// so hard links and short (8.3) names are not lost.
+    fn full_paths(
+        &mut self,
+        parser: &mut MftReader<impl ReadSeek>,
+        entry: &MftEntry,
+    ) -> Result<Vec<PyMftEntryPath>> {
+        file_names(entry)
+            .iter()
+            .map(|file_name| {
+                let resolved = self.path_for_file_name(parser, entry, file_name)?;
+                Ok(PyMftEntryPath::new(file_name, resolved))
+            })
+            .collect()
+    }
+
+    fn orphan_root(&self) -> PathBuf {
+        if self.options.mark_orphans {
+            PathBuf::from(ORPHAN_FILES)
+        } else {
+            PathBuf::from(UNKNOWN_ROOT)
+        }
+    }
+
+    fn path_for_file_name(
+        &mut self,
+        parser: &mut MftReader<impl ReadSeek>,
+        entry: &MftEntry,
+        file_name: &FileNameAttr,
+    ) -> Result<ResolvedPath> {
+        let parent_entry_id = file_name.parent.entry;
+
+        if parent_entry_id == ROOT_ENTRY_ID {
+            return Ok(ResolvedPath::new(
+                PathBuf::from(&file_name.name),
+                PathStatus::Resolved,
+            ));
+        }
+
+        if parent_entry_id == 0 || parent_entry_id == entry.header.record_number {
+            return Ok(ResolvedPath::new(
+                self.orphan_root().join(&file_name.name),
+                PathStatus::Orphan,
+            ));
+        }
+
+        let parent = self.directory_path(parser, parent_entry_id, file_name.parent.sequence)?;
+        Ok(ResolvedPath::new(
+            parent.path.join(&file_name.name),
+            parent.status,
+        ))
+    }
+
+    fn directory_path(
+        &mut self,
+        parser: &mut MftReader<impl ReadSeek>,
+        entry_id: u64,
+        sequence: u16,
+    ) -> Result<ResolvedPath> {
+        if self.visiting.contains(&entry_id) {
+            return Ok(ResolvedPath::new(self.orphan_root(), PathStatus::Loop));
+        }
+
+        let directory = match self.directories.get(&entry_id) {
+            Some(cached) => cached.clone(),
+            None => {
+                let directory = self.read_directory(parser, entry_id)?;
+                self.directories.put(entry_id, directory.clone());
+                directory
+            }
+        };
+
+        if self.options.verify_parent_sequence && !directory.is_referenced_by(sequence) {
+            return Ok(ResolvedPath::new(
+                self.orphan_root(),
+                PathStatus::SequenceMismatch,
+            ));
+        }
+
+        Ok(directory.resolved)
+    }
+
+    fn read_directory(
+        &mut self,
+        parser: &mut MftReader<impl ReadSeek>,
+        entry_id: u64,
+    ) -> Result<Directory> {
+        let missing = Directory {
+            sequence: None,
+            allocated: false,
+            resolved: ResolvedPath::new(self.orphan_root(), PathStatus::Orphan),
+        };
+
+        // A corrupted reference may point past the end of the MFT.
+        if parser
+            .get_entry_count()
+            .is_some_and(|count| entry_id >= count)
+        {
+            return Ok(missing);
+        }
+
+        if self.visiting.len() >= MAX_PATH_DEPTH {
+            return Err(Error::Any {
+                detail: format!("parent chain is deeper than {MAX_PATH_DEPTH} entries"),
+            });
+        }
+
+        let parent = match parser.get_entry(entry_id) {
+            Ok(parent) => parent,
+            // Don't mask a failing reader as an orphan.
+            Err(e @ Error::IoError { .. }) => return Err(e),
+            // Parent is maybe corrupted or incomplete, use a sentinel instead.
+            Err(_) => return Ok(missing),
+        };
+
+        self.visiting.push(entry_id);
+        let parent_path = self.full_path(parser, &parent);
+        self.visiting.pop();
+
+        let resolved = match parent_path? {
+            Some(path) if parent.is_dir() => path,
+            Some(_) => ResolvedPath::new(self.orphan_root(), PathStatus::Orphan),
+            // I have a parent, which doesn't have a filename attribute.
+            // Default to root.
+            None => ResolvedPath::new(PathBuf::new(), PathStatus::Resolved),
+        };
+
+        Ok(Directory {
+            sequence: Some(parent.header.sequence),
+            allocated: parent.is_allocated(),
+            resolved,
+        })
+    }
+}
+
+/// `mft_rs` errors only describe the outermost failure (e.g. "An I/O error has occurred"),
+/// so include their sources as well.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+
+    message
+}
@@ -0,0 +1,357 @@
+use crate::stream::RecordStream;
+use crate::ReadSeek;
+
+use byteorder::{ByteOrder, LittleEndian};
+use mft_rs::entry::{BAAD_HEADER, FILE_HEADER, ZERO_HEADER};
+use mft_rs::err::{Error, Result};
+use mft_rs::{EntryHeader, MftEntry};
+use pyo3::prelude::*;
+use serde::Serialize;
+
+use std::io::{Read, SeekFrom};
+use std::ops::Range;
+
+/// Size of the fixed part of the FILE record header.
+const ENTRY_HEADER_SIZE: usize = 48;
+/// Update sequence array fixups are applied at the end of every 512 bytes of the record.
+const SEQUENCE_NUMBER_STRIDE: usize = 512;
+
+#[pyclass]
+#[derive(Serialize, Debug, Clone)]
+/// A 512 bytes stride of a record whose last two bytes don't match the update sequence number,
+/// meaning the stride was torn (only partially written) or isn't part of the record at all.
+pub struct PyMftFixupError {
+    #[pyo3(get)]
+    pub stride: usize,
+    /// The update sequence number.
+    #[pyo3(get)]
+    pub expected: u16,
+    /// The value found at the end of the stride.
+    #[pyo3(get)]
+    pub found: u16,
+}
+
+/// A parsed record, along with the fixup errors found while parsing it.
+pub struct Record {
+    pub entry: MftEntry,
+    pub fixup_errors: Vec<PyMftFixupError>,
+}
+
+impl Record {
+    /// A placeholder for a record that was never used (zeroed).
+    pub fn empty(data: Vec<u8>, entry_id: u64) -> Self {
+        let mut header = EntryHeader::zero();
+        header.record_number = entry_id;
+
+        Record {
+            entry: MftEntry {
+                header,
+                data,
+                valid_fixup: None,
+            },
+            fixup_errors: vec![],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        &self.entry.header.signature == ZERO_HEADER
+    }
+
+    /// A record is valid if it has a FILE signature, and all of its fixups match.
+    pub fn is_valid(&self) -> bool {
+        &self.entry.header.signature == FILE_HEADER && self.fixup_errors.is_empty()
+    }
+
+    /// Returns the bytes of the record in `range` as found on disk, reverting the fixups applied
+    /// when it was parsed (empty if `range` is out of the record).
+    pub fn on_disk_bytes(&self, range: Range<usize>) -> Vec<u8> {
+        let data = &self.entry.data;
+        let mut bytes = match data.get(range.clone()) {
+            Some(bytes) => bytes.to_vec(),
+            None => return vec![],
+        };
+
+        // Empty records have no update sequence array.
+        if self.entry.header.usa_size == 0 {
+            return bytes;
+        }
+
+        // The update sequence array was checked to fit in the record when it was parsed.
+        let usa_offset = usize::from(self.entry.header.usa_offset);
+        let update_sequence = [data[usa_offset], data[usa_offset + 1]];
+
+        for stride in 0..usize::from(self.entry.header.usa_size) - 1 {
+            let stride_end = (stride + 1) * SEQUENCE_NUMBER_STRIDE;
+            let original = self
+                .fixup_errors
+                .iter()
+                .find(|e| e.stride == stride)
+                .map_or(update_sequence, |e| e.found.to_le_bytes());
+
+            for (offset, byte) in (stride_end - 2..stride_end).zip(original) {
+                if range.contains(&offset) {
+                    bytes[offset - range.start] = byte;
+                }
+            }
+        }
+
+        bytes
+    }
+
+    /// Describes why the record is not valid.
+    pub fn invalid_reason(&self) -> String {
+        if &self.entry.header.signature != FILE_HEADER {
+            format!(
+                "entry {} has a {} signature",
+                self.entry.header.record_number,
+                String::from_utf8_lossy(&self.entry.header.signature)
+            )
+        } else {
+            format!(
+                "entry {} has {} fixup mismatches (strides: {:?})",
+                self.entry.header.record_number,
+                self.fixup_errors.len(),
+                self.fixup_errors
+                    .iter()
+                    .map(|e| e.stride)
+                    .collect::<Vec<_>>()
+            )
+        }
+    }
+}
+
+/// Checks that the update sequence array fits in the record, and returns its offset and size.
+fn update_sequence_array(data: &[u8]) -> Result<(usize, usize)> {
+    if data.len() < ENTRY_HEADER_SIZE {
+        return Err(Error::Any {
+            detail: format!(
+                "record is {} bytes long, expected at least {ENTRY_HEADER_SIZE} bytes",
+                data.len()
+            ),
+        });
+    }
+
+    let usa_offset = usize::from(LittleEndian::read_u16(&data[4..6]));
+    let usa_size = usize::from(LittleEndian::read_u16(&data[6..8]));
+
+    if usa_size == 0
+        || usa_offset + usa_size * 2 > data.len()
+        || (usa_size - 1) * SEQUENCE_NUMBER_STRIDE > data.len()
+    {
+        return Err(Error::Any {
+            detail: format!(
+                "update sequence array (offset: {usa_offset}, size: {usa_size}) \
+                 does not fit in a {} bytes record",
+                data.len()
+            ),
+        });
+    }
+
+    Ok((usa_offset, usa_size))
+}
+
+/// Finds the strides of a raw record (before fixups are applied)
+/// whose last two bytes don't match the update sequence number.
+fn fixup_errors(data: &[u8]) -> Result<Vec<PyMftFixupError>> {
+    let (usa_offset, usa_size) = update_sequence_array(data)?;
+    let expected = LittleEndian::read_u16(&data[usa_offset..usa_offset + 2]);
+
+    Ok((0..usa_size - 1)
+        .filter_map(|stride| {
+            let stride_end = (stride + 1) * SEQUENCE_NUMBER_STRIDE;
+            let found = LittleEndian::read_u16(&data[stride_end - 2..stride_end]);
+
+            if found != expected {
+                Some(PyMftFixupError {
+                    stride,
+                    expected,
+                    found,
+                })
+            } else {
+                None
+            }
+        })
+        .collect())
+}
+
+/// Parses a single raw MFT record.
+///
+/// `MftEntry::from_buffer` trusts the update sequence array offset and size found in the header,
+/// so they are checked against the buffer first, since the record could be any arbitrary data.
+pub fn parse_record(mut data: Vec<u8>, entry_id: u64) -> Result<Record> {
+    let signature = match data.get(..4) {
+        Some(signature) if signature == FILE_HEADER || signature == BAAD_HEADER => {
+            [signature[0], signature[1], signature[2], signature[3]]
+        }
+        bad_sig => {
+            return Err(Error::InvalidEntrySignature {
+                bad_sig: bad_sig.unwrap_or(&data).to_vec(),
+            })
+        }
+    };
+
+    let fixup_errors = fixup_errors(&data)?;
+
+    // `MftEntry::from_buffer` only applies fixups to FILE records,
+    // but BAAD records share the same layout.
+    data[..4].copy_from_slice(FILE_HEADER);
+
+    let mut entry = MftEntry::from_buffer(data, entry_id)?;
+    entry.header.signature = signature;
+    entry.data[..4].copy_from_slice(&signature);
+
+    Ok(Record {
+        entry,
+        fixup_errors,
+    })
+}
+
+/// Reads records out of an MFT.
+///
+/// Works like `MftParser`, but keeps the raw record bytes around until they are parsed,
+/// since `MftParser` overwrites the end of every stride with its fixup
+/// (losing the torn bytes of records with fixup errors).
+pub struct MftReader<T: ReadSeek> {
+    data: Data<T>,
+    /// Entry size is present in the volume header, but this is not available to us.
+    /// Instead this will be guessed by the entry size of the first entry.
+    entry_size: u32,
+}
+
+enum Data<T> {
+    Seekable {
+        data: T,
+        size: u64,
+        // Next expected read offset in the underlying stream. Used to avoid a seek syscall when
+        // reading entries sequentially.
+        next_read_offset: u64,
+    },
+    /// Records can only be read in order.
+    Stream(RecordStream),
+}
+
+/// Guesses the entry size of all the records from the first one.
+fn entry_size(first_entry: &EntryHeader) -> Result<u32> {
+    if &first_entry.signature == ZERO_HEADER || first_entry.total_entry_size == 0 {
+        return Err(Error::Any {
+            detail: "the first entry is empty, cannot determine the entry size".to_string(),
+        });
+    }
+
+    Ok(first_entry.total_entry_size)
+}
+
+impl<T: ReadSeek> MftReader<T> {
+    pub fn from_read_seek(mut data: T, size: Option<u64>) -> Result<Self> {
+        // We use the first entry to guess the entry size for all the other records.
+        let first_entry = EntryHeader::from_reader(&mut data, 0)?;
+        let entry_size = entry_size(&first_entry)?;
+
+        let size = match size {
+            Some(sz) => sz,
+            None => data.seek(SeekFrom::End(0))?,
+        };
+
+        data.rewind()?;
+
+        Ok(MftReader {
+            data: Data::Seekable {
+                data,
+                size,
+                next_read_offset: 0,
+            },
+            entry_size,
+        })
+    }
+
+    /// Reads the MFT from a stream which cannot seek, records can then only be iterated over
+    /// in order (see `RecordStream` for which records path resolution can still read).
+    pub fn from_stream(mut data: Box<dyn Read + Send>) -> Result<Self> {
+        let mut header = [0; ENTRY_HEADER_SIZE];
+        data.read_exact(&mut header)?;
+
+        let first_entry = EntryHeader::from_reader(&mut &header[..], 0)?;
+        let entry_size = entry_size(&first_entry)?;
+
+        if (entry_size as usize) < ENTRY_HEADER_SIZE {
+            return Err(Error::Any {
+                detail: format!(
+                    "the first entry is {entry_size} bytes long, \
+                     expected at least {ENTRY_HEADER_SIZE} bytes"
+                ),
+            });
+        }
+
+        let mut first_record = header.to_vec();
+        first_record.resize(entry_size as usize, 0);
+        data.read_exact(&mut first_record[ENTRY_HEADER_SIZE..])?;
+
+        Ok(MftReader {
+            data: Data::Stream(RecordStream::new(data, first_record)),
+            entry_size,
+        })
+    }
+
+    /// The number of records, or `None` for a stream, whose size is unknown until it is read.
+    pub fn get_entry_count(&self) -> Option<u64> {
+        match &self.data {
+            Data::Seekable { size, .. } => Some(size / u64::from(self.entry_size)),
+            Data::Stream(_) => None,
+        }
+    }
+
+    /// Whether `entry_number` is past the last record. Streams are read up to it to find out.
+    pub fn is_past_end(&mut self, entry_number: u64) -> Result<bool> {
+        match &mut self.data {
+            Data::Seekable { size, .. } => Ok(entry_number >= *size / u64::from(self.entry_size)),
+            Data::Stream(stream) => stream.is_past_end(entry_number),
+        }
+    }
+
+    /// Reads the raw bytes of a record (before fixups are applied) by entry number,
+    /// to iterate over it. Records of a stream must be read in order.
+    pub fn read_record(&mut self, entry_number: u64) -> Result<Vec<u8>> {
+        let (data, next_read_offset) = match &mut self.data {
+            Data::Seekable {
+                data,
+                next_read_offset,
+                ..
+            } => (data, next_read_offset),
+            Data::Stream(stream) => return stream.take_record(entry_number),
+        };
+
+        let entry_size = u64::from(self.entry_size);
+        let desired_offset = entry_number * entry_size;
+
+        if *next_read_offset != desired_offset {
+            data.seek(SeekFrom::Start(desired_offset))?;
+            *next_read_offset = desired_offset;
+        }
+
+        let mut record = vec![0; self.entry_size as usize];
+
+        if let Err(e) = data.read_exact(&mut record) {
+            // The position of the stream is unknown after a failed read.
+            *next_read_offset = u64::MAX;
+            return Err(e.into());
+        }
+        *next_read_offset = next_read_offset.saturating_add(entry_size);
+
+        Ok(record)
+    }
+
+    /// Reads and parses a record by entry number, to look it up while resolving paths.
+    pub fn get_record(&mut self, entry_number: u64) -> Result<Record> {
+        let data = match &mut self.data {
+            Data::Seekable { .. } => self.read_record(entry_number)?,
+            Data::Stream(stream) => stream.get_record(entry_number)?,
+        };
+
+        parse_record(data, entry_number)
+    }
+
+    /// Reads an entry from the MFT by entry number.
+    pub fn get_entry(&mut self, entry_number: u64) -> Result<MftEntry> {
+        self.get_record(entry_number).map(|record| record.entry)
+    }
+}
@@ -0,0 +1,97 @@
+use byteorder::{ByteOrder, LittleEndian};
+use mft_rs::attribute::header::{MftAttributeHeader, ResidentialHeader};
+use mft_rs::attribute::{MftAttributeContent, MftAttributeType};
+use mft_rs::MftAttribute;
+use num_traits::FromPrimitive;
+
+use std::io::{Cursor, Seek, SeekFrom};
+
+/// Attributes are aligned to 8 bytes in the record.
+const ATTRIBUTE_ALIGNMENT: usize = 8;
+/// Size of the header of a resident attribute (without its name).
+const RESIDENT_HEADER_SIZE: usize = 0x18;
+/// Offset of the name length in the content of a $FILE_NAME attribute.
+const FILE_NAME_LENGTH_OFFSET: usize = 0x40;
+/// Offset of the name in the content of a $FILE_NAME attribute.
+const FILE_NAME_OFFSET: usize = 0x42;
+
+/// Returns the bytes of the record after the used part of the record.
+pub fn slack(record: &[u8], used_entry_size: u32) -> &[u8] {
+    record.get(used_entry_size as usize..).unwrap_or_default()
+}
+
+/// Looks for resident $FILE_NAME and $DATA attributes left over in the slack of the record,
+/// from before the record was reused or shrunk.
+///
+/// Residual attributes are often partially overwritten, so only those whose header and content
+/// fit in the record and parse successfully are returned.
+pub fn residual_attributes(record: &[u8], used_entry_size: u32) -> Vec<MftAttribute> {
+    let mut attributes = vec![];
+    let mut offset = (used_entry_size as usize).next_multiple_of(ATTRIBUTE_ALIGNMENT);
+
+    while offset + RESIDENT_HEADER_SIZE <= record.len() {
+        match residual_attribute(record, offset) {
+            Some(attribute) => {
+                offset += attribute.header.record_length as usize;
+                attributes.push(attribute);
+            }
+            None => offset += ATTRIBUTE_ALIGNMENT,
+        }
+    }
+
+    attributes
+}
+
+/// Parses the residual attribute at `offset`, if there seems to be one.
+fn residual_attribute(record: &[u8], offset: usize) -> Option<MftAttribute> {
+    let header = &record[offset..offset + RESIDENT_HEADER_SIZE];
+
+    let type_code = MftAttributeType::from_u32(LittleEndian::read_u32(&header[0..4]));
+    let record_length = LittleEndian::read_u32(&header[4..8]) as usize;
+    let form_code = header[8];
+    let data_size = LittleEndian::read_u32(&header[0x10..0x14]) as usize;
+    let data_offset = usize::from(LittleEndian::read_u16(&header[0x14..0x16]));
+
+    // Only the attribute types which hold historical data are looked for,
+    // other types are too likely to match random data.
+    let plausible = matches!(
+        type_code,
+        Some(MftAttributeType::FileName | MftAttributeType::DATA)
+    ) && form_code == 0
+        && record_length.is_multiple_of(ATTRIBUTE_ALIGNMENT)
+        && offset + record_length <= record.len()
+        && data_offset >= RESIDENT_HEADER_SIZE
+        && data_offset + data_size <= record_length;
+
+    if !plausible {
+        return None;
+    }
+
+    let attribute = &record[..offset + record_length];
+
+    if type_code == Some(MftAttributeType::FileName) {
+        let name_length = *attribute.get(offset + data_offset + FILE_NAME_LENGTH_OFFSET)?;
+
+        if FILE_NAME_OFFSET + usize::from(name_length) * 2 > data_size {
+            return None;
+        }
+    }
+
+    // The cursor ends with the attribute, so that parsing can't read past it.
+    let mut cursor = Cursor::new(attribute);
+    cursor.seek(SeekFrom::Start(offset as u64)).ok()?;
+
+    let header = MftAttributeHeader::from_stream(&mut cursor).ok()??;
+
+    let data = match header.residential_header {
+        ResidentialHeader::Resident(ref resident) => {
+            cursor
+                .seek(SeekFrom::Start((offset + data_offset) as u64))
+                .ok()?;
+            MftAttributeContent::from_stream_resident(&mut cursor, &header, resident).ok()?
+        }
+        ResidentialHeader::NonResident(_) => return None,
+    };
+
+    Some(MftAttribute { header, data })
+}
@@ -0,0 +1,138 @@
+use byteorder::{ByteOrder, LittleEndian};
+use mft_rs::entry::{BAAD_HEADER, FILE_HEADER};
+use mft_rs::err::{Error, Result};
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read};
+
+/// Offset of the flags in the record header.
+const FLAGS_OFFSET: usize = 22;
+/// Set in the record flags of directories.
+const DIRECTORY_FLAG: u16 = 0x02;
+/// How many bytes of records are read ahead at most to find a parent, past the records not
+/// iterated over yet. Parents further ahead are treated like missing ones, so a corrupt
+/// reference can't pull the rest of the stream into memory.
+const MAX_READ_AHEAD: u64 = 16 * 1024 * 1024;
+
+/// The records of an MFT read from a stream which cannot seek (a pipe, a socket, a decompressor...).
+///
+/// Records are read in order, and only the ones path resolution may need again are kept:
+/// directories, and the records read ahead to find a parent which comes after its children
+/// (until they are iterated over, and within `MAX_READ_AHEAD`).
+pub struct RecordStream {
+    data: Box<dyn Read + Send>,
+    entry_size: usize,
+    /// Number of records read from the stream so far.
+    records_read: u64,
+    /// Whether the end of the stream was reached (or reading from it failed).
+    ended: bool,
+    /// Records read from the stream, but not iterated over yet.
+    ahead: BTreeMap<u64, Vec<u8>>,
+    /// Directory records which were iterated over.
+    directories: HashMap<u64, Vec<u8>>,
+}
+
+impl RecordStream {
+    /// `first_record` was already read from `data`, to find out the entry size.
+    pub fn new(data: Box<dyn Read + Send>, first_record: Vec<u8>) -> Self {
+        RecordStream {
+            data,
+            entry_size: first_record.len(),
+            records_read: 1,
+            ended: false,
+            ahead: BTreeMap::from([(0, first_record)]),
+            directories: HashMap::new(),
+        }
+    }
+
+    /// Reads records from the stream until `entry_number` was read, or the stream ended.
+    fn read_up_to(&mut self, entry_number: u64) -> Result<()> {
+        while !self.ended && self.records_read <= entry_number {
+            let mut record = vec![0; self.entry_size];
+
+            match self.data.read_exact(&mut record) {
+                Ok(()) => {
+                    self.ahead.insert(self.records_read, record);
+                    self.records_read += 1;
+                }
+                // Like the end of a file, a trailing partial record is ignored.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => self.ended = true,
+                Err(e) => {
+                    self.ended = true;
+                    return Err(e.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the stream ends before `entry_number`.
+    pub fn is_past_end(&mut self, entry_number: u64) -> Result<bool> {
+        self.read_up_to(entry_number)?;
+
+        Ok(entry_number >= self.records_read)
+    }
+
+    /// Takes the raw bytes of a record to iterate over it. Records must be taken in order.
+    pub fn take_record(&mut self, entry_number: u64) -> Result<Vec<u8>> {
+        self.read_up_to(entry_number)?;
+
+        let record = self
+            .ahead
+            .remove(&entry_number)
+            .ok_or_else(|| self.unavailable(entry_number))?;
+
+        if is_directory(&record) {
+            self.directories.insert(entry_number, record.clone());
+        }
+
+        Ok(record)
+    }
+
+    /// Reads the raw bytes of a record to resolve paths with, reading ahead if needed.
+    /// Records which were iterated over are only available if they are directories.
+    pub fn get_record(&mut self, entry_number: u64) -> Result<Vec<u8>> {
+        if entry_number >= self.records_read {
+            let next_to_iterate = self.ahead.keys().next().copied();
+            let window_start = next_to_iterate.unwrap_or(self.records_read);
+
+            if (entry_number - window_start).saturating_add(1) > self.max_records_ahead() {
+                return Err(Error::Any {
+                    detail: format!(
+                        "entry {entry_number} is too far ahead in the stream to be read"
+                    ),
+                });
+            }
+        }
+
+        self.read_up_to(entry_number)?;
+
+        self.ahead
+            .get(&entry_number)
+            .or_else(|| self.directories.get(&entry_number))
+            .cloned()
+            .ok_or_else(|| self.unavailable(entry_number))
+    }
+
+    fn max_records_ahead(&self) -> u64 {
+        (MAX_READ_AHEAD / self.entry_size as u64).max(1)
+    }
+
+    fn unavailable(&self, entry_number: u64) -> Error {
+        let detail = if entry_number >= self.records_read {
+            format!("entry {entry_number} is past the end of the stream")
+        } else {
+            format!("entry {entry_number} was already read from the stream")
+        };
+
+        Error::Any { detail }
+    }
+}
+
+fn is_directory(record: &[u8]) -> bool {
+    let signature = &record[..4];
+
+    (signature == FILE_HEADER || signature == BAAD_HEADER)
+        && LittleEndian::read_u16(&record[FLAGS_OFFSET..FLAGS_OFFSET + 2]) & DIRECTORY_FLAG != 0
+}
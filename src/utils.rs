@@ -1,35 +1,344 @@
 use log::{Level, Log, Metadata, Record, SetLoggerError};
 
+use crate::compression::{read_magic, Compression};
+use crate::ReadSeek;
+use memmap2::Mmap;
 use mft_rs::Timestamp;
+#[cfg(any(not(Py_LIMITED_API), Py_3_11))]
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{PyAnyMethods, PyString, PyStringMethods};
+use pyo3::types::{PyAnyMethods, PyBytes, PyString, PyStringMethods};
 use pyo3_file::PyFileLikeObject;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::slice;
+use std::sync::Arc;
+
+/// The size of the read buffer of files, unless set otherwise.
+pub const DEFAULT_BUFFER_SIZE: usize = 4096;
+
+/// How files opened from a path are read.
+#[derive(Debug, Clone, Copy)]
+pub struct FileOptions {
+    /// Memory map the file, instead of reading it through a buffer.
+    pub mmap: bool,
+    pub buffer_size: usize,
+}
+
+impl Default for FileOptions {
+    fn default() -> Self {
+        FileOptions {
+            mmap: false,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+        }
+    }
+}
+
+impl FileOptions {
+    pub fn new(mmap: bool, buffer_size: usize) -> PyResult<Self> {
+        if buffer_size == 0 {
+            return Err(PyValueError::new_err("buffer_size must be positive"));
+        }
+
+        Ok(FileOptions { mmap, buffer_size })
+    }
+}
+
+/// A `BufReader` which keeps its buffer when seeking to a position within it,
+/// since `BufReader::seek` always discards it (and path resolution seeks back and forth).
+pub struct SeekableBufReader<R> {
+    inner: BufReader<R>,
+    position: u64,
+}
+
+impl<R: Read + Seek> SeekableBufReader<R> {
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        SeekableBufReader {
+            inner: BufReader::with_capacity(capacity, inner),
+            position: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for SeekableBufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableBufReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(target) => target,
+            SeekFrom::Current(offset) => {
+                self.position.checked_add_signed(offset).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid seek to a negative position",
+                    )
+                })?
+            }
+            // The end is only known by the inner reader.
+            SeekFrom::End(_) => {
+                self.position = self.inner.seek(pos)?;
+                return Ok(self.position);
+            }
+        };
+
+        // Only seeks the inner reader (discarding the buffer) if `target` is outside of the buffer.
+        self.inner
+            .seek_relative(target.wrapping_sub(self.position) as i64)?;
+        self.position = target;
+
+        Ok(target)
+    }
+}
+
+/// The data of a python object supporting the buffer protocol (`bytes`, `bytearray`,
+/// `memoryview`, numpy arrays...). Read-only data is read in place, other data is copied,
+/// since python code could modify it while it is read (without the GIL).
+#[derive(Clone)]
+pub struct PyMemory(Arc<PyMemoryInner>);
+
+struct PyMemoryInner {
+    /// Keeps the data alive (and, for buffers, exported, so it cannot be resized).
+    _owner: PyMemoryOwner,
+    data: *const u8,
+    len: usize,
+}
+
+// Only held, never read.
+#[allow(dead_code)]
+enum PyMemoryOwner {
+    Bytes(Py<PyBytes>),
+    #[cfg(any(not(Py_LIMITED_API), Py_3_11))]
+    Buffer(PyBuffer<u8>),
+}
+
+// Safety: the data is owned by `_owner`, which can be sent between threads,
+// and is read-only, so it cannot change while it is read.
+unsafe impl Send for PyMemoryInner {}
+unsafe impl Sync for PyMemoryInner {}
+
+impl PyMemory {
+    /// Returns the data of `obj`, if it supports the buffer protocol.
+    fn from_pyobject(obj: &Bound<'_, PyAny>) -> PyResult<Option<Self>> {
+        if let Ok(bytes) = obj.downcast::<PyBytes>() {
+            return Ok(Some(Self::from_bytes(bytes.clone())));
+        }
+
+        let memoryview = obj.py().import("builtins")?.getattr("memoryview")?;
+
+        let view = match memoryview.call1((obj,)) {
+            Ok(view) => view,
+            // Does not support the buffer protocol.
+            Err(err) if err.is_instance_of::<PyTypeError>(obj.py()) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        if !view.getattr("c_contiguous")?.extract::<bool>()? {
+            return Err(PyValueError::new_err("buffers must be contiguous"));
+        }
+
+        // The bytes of the buffer, whatever the format of its items is.
+        let view = view.call_method1("cast", ("B",))?;
+
+        if view.getattr("readonly")?.extract::<bool>()? {
+            if let Some(memory) = Self::from_readonly_buffer(&view)? {
+                return Ok(Some(memory));
+            }
+        }
+
+        let bytes = view.call_method0("tobytes")?.downcast_into::<PyBytes>()?;
+
+        Ok(Some(Self::from_bytes(bytes)))
+    }
+
+    fn from_bytes(bytes: Bound<'_, PyBytes>) -> Self {
+        let data = bytes.as_bytes();
+
+        PyMemory(Arc::new(PyMemoryInner {
+            data: data.as_ptr(),
+            len: data.len(),
+            _owner: PyMemoryOwner::Bytes(bytes.unbind()),
+        }))
+    }
+
+    #[cfg(any(not(Py_LIMITED_API), Py_3_11))]
+    fn from_readonly_buffer(view: &Bound<'_, PyAny>) -> PyResult<Option<Self>> {
+        let buffer = PyBuffer::<u8>::get(view)?;
+
+        Ok(Some(PyMemory(Arc::new(PyMemoryInner {
+            data: buffer.buf_ptr() as *const u8,
+            len: buffer.len_bytes(),
+            _owner: PyMemoryOwner::Buffer(buffer),
+        }))))
+    }
+
+    /// The buffer protocol is only part of the limited API since python 3.11,
+    /// before that buffers other than `bytes` are copied.
+    #[cfg(not(any(not(Py_LIMITED_API), Py_3_11)))]
+    fn from_readonly_buffer(_view: &Bound<'_, PyAny>) -> PyResult<Option<Self>> {
+        Ok(None)
+    }
+}
+
+impl AsRef<[u8]> for PyMemory {
+    fn as_ref(&self) -> &[u8] {
+        if self.0.len == 0 {
+            return &[];
+        }
+
+        // Safety: the data is kept alive (and unmoved) by the owner for as long as `self` is.
+        unsafe { slice::from_raw_parts(self.0.data, self.0.len) }
+    }
+}
+
+impl fmt::Debug for PyMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PyMemory")
+            .field("len", &self.0.len)
+            .finish()
+    }
+}
 
 #[derive(Debug)]
 pub enum FileOrFileLike {
-    File(String),
+    File(PathBuf),
+    Memory(PyMemory),
     FileLike(PyFileLikeObject),
+    /// A file-like object which cannot seek (a pipe, a socket...).
+    Stream(PyFileLikeObject),
 }
 
 impl FileOrFileLike {
     pub fn from_pyobject(path_or_file_like: Py<PyAny>) -> PyResult<FileOrFileLike> {
         Python::attach(|py| {
-            // Is a path
-            let maybe_path: Option<String> = {
+            {
+                let bound = path_or_file_like.bind(py);
+
+                // Is a path
+                if let Ok(path) = bound.downcast::<PyString>() {
+                    let path = path.to_string_lossy().into_owned();
+                    return Ok(FileOrFileLike::File(path.into()));
+                }
+
+                // Is a `pathlib.Path` (or any other `os.PathLike`)
+                if bound.hasattr("__fspath__")? {
+                    return Ok(FileOrFileLike::File(bound.extract()?));
+                }
+
+                // Is data in memory
+                if let Some(memory) = PyMemory::from_pyobject(bound)? {
+                    return Ok(FileOrFileLike::Memory(memory));
+                }
+            }
+
+            let seekable = {
                 let bound = path_or_file_like.bind(py);
-                bound
-                    .downcast::<PyString>()
-                    .ok()
-                    .map(|s| s.to_string_lossy().into_owned())
+
+                bound.hasattr("seek")?
+                    && (!bound.hasattr("seekable")?
+                        || bound.call_method0("seekable")?.is_truthy()?)
             };
 
-            if let Some(path) = maybe_path {
-                return Ok(FileOrFileLike::File(path));
+            // We only need read + seek (no write, no fileno), or only read for streams.
+            let file_like =
+                PyFileLikeObject::with_requirements(path_or_file_like, true, false, false, false)?;
+
+            Ok(if seekable {
+                FileOrFileLike::FileLike(file_like)
+            } else {
+                FileOrFileLike::Stream(file_like)
+            })
+        })
+    }
+
+    /// Returns another source of the same data, so it can be read independently.
+    /// Python file-like objects cannot be read again.
+    pub fn try_clone(&self) -> Option<FileOrFileLike> {
+        match self {
+            FileOrFileLike::File(path) => Some(FileOrFileLike::File(path.clone())),
+            FileOrFileLike::Memory(memory) => Some(FileOrFileLike::Memory(memory.clone())),
+            FileOrFileLike::FileLike(_) | FileOrFileLike::Stream(_) => None,
+        }
+    }
+
+    /// Detects the compression of the data from its magic bytes.
+    /// Streams can't be peeked at, their compression is detected when they are read.
+    pub fn compression(&mut self) -> PyResult<Option<Compression>> {
+        let magic = match self {
+            FileOrFileLike::File(path) => read_magic(&mut File::open(path)?)?,
+            FileOrFileLike::Memory(memory) => read_magic(&mut memory.as_ref())?,
+            FileOrFileLike::FileLike(f) => {
+                let position = f.stream_position()?;
+                let magic = read_magic(f)?;
+                f.seek(SeekFrom::Start(position))?;
+
+                magic
             }
+            FileOrFileLike::Stream(_) => return Ok(None),
+        };
+
+        Ok(Compression::detect(&magic))
+    }
+
+    /// Opens the file (if needed), returns a reader and the size of the data if it is known.
+    /// `options` only apply to files opened from a path, data in memory is read in place.
+    pub fn into_read_seek(
+        self,
+        options: FileOptions,
+    ) -> PyResult<(Box<dyn ReadSeek + Send>, Option<u64>)> {
+        match self {
+            FileOrFileLike::File(s) => {
+                let file = File::open(s)?;
+                let size = file.metadata()?.len();
+
+                if options.mmap {
+                    // Safety: like any memory mapped file, it must not be truncated while mapped.
+                    let mmap = unsafe { Mmap::map(&file)? };
+
+                    return Ok((
+                        Box::new(Cursor::new(mmap)) as Box<dyn ReadSeek + Send>,
+                        Some(size),
+                    ));
+                }
+
+                let reader = SeekableBufReader::with_capacity(options.buffer_size, file);
+
+                Ok((Box::new(reader) as Box<dyn ReadSeek + Send>, Some(size)))
+            }
+            FileOrFileLike::Memory(memory) => {
+                let size = memory.as_ref().len() as u64;
+
+                Ok((
+                    Box::new(Cursor::new(memory)) as Box<dyn ReadSeek + Send>,
+                    Some(size),
+                ))
+            }
+            FileOrFileLike::FileLike(f) => Ok((Box::new(f) as Box<dyn ReadSeek + Send>, None)),
+            FileOrFileLike::Stream(_) => Err(PyValueError::new_err(
+                "the file-like object is not seekable",
+            )),
+        }
+    }
 
-            // We only need read + seek (no write, no fileno).
-            PyFileLikeObject::with_requirements(path_or_file_like, true, false, true, false)
-                .map(FileOrFileLike::FileLike)
+    /// Opens the file (if needed), and returns a reader which is only read sequentially.
+    /// `options.buffer_size` applies to files opened from a path.
+    pub fn into_read(self, options: FileOptions) -> PyResult<Box<dyn Read + Send>> {
+        Ok(match self {
+            FileOrFileLike::File(path) => Box::new(BufReader::with_capacity(
+                options.buffer_size,
+                File::open(path)?,
+            )),
+            FileOrFileLike::Memory(memory) => Box::new(Cursor::new(memory)),
+            FileOrFileLike::FileLike(f) | FileOrFileLike::Stream(f) => Box::new(f),
         })
     }
 }
@@ -1,10 +1,19 @@
+import array
+import csv
 import datetime
+import gzip
+import io
+import json
+import lzma
+import struct
+import zipfile
 
 import pytest
 
+from concurrent.futures import ThreadPoolExecutor
 from pathlib import Path
 
-from mft import PyMftParser, PyMftEntry
+from mft import PyMftParser, PyMftEntry, carve_entries, parse_entry
 
 
 @pytest.fixture
@@ -15,6 +24,19 @@ def sample_mft() -> Path:
     return p
 
 
+def patch_parent_reference(mft: bytearray, entry_id: int, parent_id: int, parent_sequence: int):
+    """Points every $FILE_NAME attribute of an entry at a different parent."""
+    record = entry_id * 1024
+    offset = record + struct.unpack_from("<H", mft, record + 0x14)[0]
+
+    while struct.unpack_from("<I", mft, offset)[0] != 0xFFFFFFFF:
+        attribute_type, attribute_length = struct.unpack_from("<II", mft, offset)
+        if attribute_type == 0x30:
+            content = offset + struct.unpack_from("<H", mft, offset + 0x14)[0]
+            struct.pack_into("<Q", mft, content, parent_id | (parent_sequence << 48))
+        offset += attribute_length
+
+
 def test_it_works(sample_mft: Path):
     with open(sample_mft, "rb") as m:
         parser = PyMftParser(m)
@@ -59,3 +81,805 @@ def test_doesnt_yield_zeroed_entries(sample_mft: Path):
             assert False, (e, entry.entry_id)
 
 
+
+def test_full_paths_include_dos_names(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft))
+
+    for entry in parser.entries():
+        if entry.entry_id == 62:
+            break
+
+    namespaces = {p.namespace: p.path for p in entry.full_paths}
+    assert namespaces["Win32"] == entry.full_path
+    assert namespaces["DOS"].endswith("REPOSI~1")
+
+
+def test_stale_parents_are_marked_as_orphans(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft), verify_parent_sequence=True, mark_orphans=True)
+
+    stale = [e for e in parser.entries() if e.path_status == "sequence-mismatch"]
+
+    assert stale
+    assert all(e.full_path.startswith("$OrphanFiles") for e in stale)
+
+
+def test_parent_loop_is_detected(sample_mft: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    patch_parent_reference(mft, 64, 65, 1)
+    patch_parent_reference(mft, 65, 64, 1)
+
+    parser = PyMftParser(io.BytesIO(bytes(mft)))
+
+    for entry in parser.entries():
+        if entry.entry_id == 64:
+            break
+
+    assert entry.path_status == "loop"
+    assert all(p.status == "loop" for p in entry.full_paths)
+
+
+class FailingReader(io.BytesIO):
+    """Fails to read a single MFT record, like a flaky file-like object would."""
+
+    def __init__(self, data: bytes, failing_entry_id: int):
+        super().__init__(data)
+        self.failing_offset = failing_entry_id * 1024
+
+    def read(self, size=-1):
+        if self.tell() == self.failing_offset:
+            raise OSError("failed to read parent")
+        return super().read(size)
+
+
+def test_io_error_while_resolving_path_is_surfaced_on_entry(sample_mft: Path):
+    parser = PyMftParser(FailingReader(sample_mft.read_bytes(), 3606))
+
+    entries = [e for e in parser.entries() if isinstance(e, PyMftEntry)]
+    entry = next(e for e in entries if e.entry_id == 671)
+
+    assert entry.path_status == "error"
+    assert "failed to read parent" in entry.path_error
+    assert entries[-1].entry_id > 3606
+
+
+def test_parent_out_of_bounds_is_an_orphan(sample_mft: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    patch_parent_reference(mft, 671, 0xFFFFFFFFFF, 1)
+
+    parser = PyMftParser(io.BytesIO(bytes(mft)))
+    entry = next(e for e in parser.entries() if e.entry_id == 671)
+
+    assert entry.path_status == "orphan"
+    assert entry.path_error is None
+    assert entry.full_path.startswith("[Unknown]")
+
+
+def test_corrupted_parent_is_an_orphan(sample_mft: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    mft[3606 * 1024 : 3606 * 1024 + 4] = b"JUNK"
+
+    parser = PyMftParser(io.BytesIO(bytes(mft)), mark_orphans=True)
+    entry = next(e for e in parser.entries() if e.entry_id == 671)
+
+    assert entry.path_status == "orphan"
+    assert entry.full_path.startswith("$OrphanFiles")
+    assert entry.full_path.endswith("Internet Explorer")
+
+
+def test_parse_single_entry(sample_mft: Path):
+    record = sample_mft.read_bytes()[671 * 1024 : 672 * 1024]
+
+    entry = parse_entry(record, 671)
+
+    assert entry.entry_id == 671
+    assert entry.full_path == "Internet Explorer"
+    assert entry.path_status == "orphan"
+    assert len(list(entry.attributes())) > 0
+
+
+@pytest.mark.parametrize(
+    "record",
+    [
+        b"",
+        b"JUNK" + b"\x00" * 1020,
+        # Update sequence array pointing outside of the record.
+        b"FILE" + struct.pack("<HH", 0xFFF0, 3) + b"\x00" * 1016,
+    ],
+)
+def test_parse_invalid_entry_raises(record: bytes):
+    with pytest.raises(RuntimeError):
+        parse_entry(record)
+
+
+def test_carve_entries_from_blob(sample_mft: Path):
+    mft = sample_mft.read_bytes()
+    blob = b"\xAA" * 1536 + mft[:4 * 1024] + b"FILE" + b"\x00" * 2044 + mft[671 * 1024 : 672 * 1024]
+
+    entries = list(carve_entries(io.BytesIO(blob)))
+
+    assert [e.entry_id for e in entries] == [0, 1, 2, 3, 671]
+    assert [e.offset for e in entries] == [1536, 2560, 3584, 4608, 7680]
+    assert entries[0].full_path == "$MFT"
+    assert entries[-1].full_path == "Internet Explorer"
+
+
+def test_carve_entries_with_unaligned_records(sample_mft: Path):
+    blob = b"\xAA" * 100 + sample_mft.read_bytes()[:1024]
+
+    assert list(carve_entries(io.BytesIO(blob))) == []
+    assert [e.offset for e in carve_entries(io.BytesIO(blob), alignment=4)] == [100]
+
+
+def test_strict_yields_invalid_records_as_errors(sample_mft: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    mft[670 * 1024 : 670 * 1024 + 4] = b"BAAD"
+    mft[672 * 1024 - 2 : 672 * 1024] = b"\xDE\xAD"
+
+    parser = PyMftParser(io.BytesIO(bytes(mft)))
+    assert all(isinstance(e, PyMftEntry) for e in parser.entries())
+
+    parser = PyMftParser(io.BytesIO(bytes(mft)))
+    errors = [e for e in parser.entries(strict=True) if isinstance(e, Exception)]
+
+    assert [e.entry_id for e in errors] == [670, 671]
+    assert "BAAD" in str(errors[0])
+    assert "fixup" in str(errors[1])
+
+
+def test_parse_errors_have_an_entry_id(sample_mft: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    mft[3606 * 1024 : 3606 * 1024 + 4] = b"JUNK"
+
+    parser = PyMftParser(io.BytesIO(bytes(mft)))
+    errors = [e for e in parser.entries() if isinstance(e, Exception)]
+
+    assert [e.entry_id for e in errors] == [3606]
+    assert "entry 3606" in str(errors[0])
+
+
+def test_invalid_records(sample_mft: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    mft[670 * 1024 : 670 * 1024 + 4] = b"BAAD"
+    mft[672 * 1024 - 2 : 672 * 1024] = b"\xDE\xAD"
+
+    parser = PyMftParser(io.BytesIO(bytes(mft)))
+    entries = {e.entry_id: e for e in parser.entries()}
+
+    assert not entries[670].valid
+    assert entries[670].fixup_errors == []
+
+    torn = entries[671]
+    assert not torn.valid
+    assert [(e.stride, e.found) for e in torn.fixup_errors] == [(1, 0xADDE)]
+    assert torn.full_path.endswith("Internet Explorer")
+    assert len(list(torn.attributes())) > 0
+
+    assert entries[672].valid
+    assert entries[672].fixup_errors == []
+
+
+def test_invalid_records_json(sample_mft: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    mft[672 * 1024 - 2 : 672 * 1024] = b"\xDE\xAD"
+
+    parser = PyMftParser(io.BytesIO(bytes(mft)))
+    records = [json.loads(r) for r in parser.entries_json()]
+    torn = next(r for r in records if r["header"]["record_number"] == 671)
+
+    assert torn["valid"] is False
+    assert [(e["stride"], e["found"]) for e in torn["fixup_errors"]] == [(1, 0xADDE)]
+
+
+def test_include_empty_records(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft))
+    number_of_entries = parser.number_of_entries()
+    entries = list(parser.entries(include_empty=True))
+
+    assert [e.entry_id for e in entries] == list(range(number_of_entries))
+
+    empty = [e for e in entries if e.empty]
+    assert len(empty) > 0
+    assert empty[0].entry_id == 16
+    assert empty[0].offset == 16 * 1024
+    assert not empty[0].valid
+    assert list(empty[0].attributes()) == []
+
+
+def test_include_empty_records_csv(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft))
+    lines = b"".join(parser.entries_csv(include_empty=True)).decode().splitlines()
+
+    assert lines[17].startswith(",16,")
+
+
+def test_raw_bytes(sample_mft: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    mft[672 * 1024 - 2 : 672 * 1024] = b"\xDE\xAD"
+
+    parser = PyMftParser(io.BytesIO(bytes(mft)))
+    entry = next(e for e in parser.entries() if e.entry_id == 671)
+
+    assert entry.raw_bytes() == mft[671 * 1024 : 672 * 1024]
+
+    fixed_up = entry.raw_bytes(fixups=True)
+    assert len(fixed_up) == 1024
+    assert fixed_up[510:512] != mft[671 * 1024 + 510 : 671 * 1024 + 512]
+
+    for attribute in entry.attributes():
+        raw = attribute.raw_bytes()
+        assert len(raw) == attribute.data_size
+        assert struct.unpack_from("<I", raw)[0] == attribute.type_code
+        assert raw in entry.raw_bytes()
+
+
+def test_slack_attributes(sample_mft: Path):
+    mft = sample_mft.read_bytes()
+    dos_name = next(a for a in parse_entry(mft[62 * 1024 : 63 * 1024]).attributes() if a.type_code == 0x30)
+    residual = dos_name.raw_bytes(fixups=True)
+
+    record = bytearray(mft[671 * 1024 : 672 * 1024])
+    used_entry_size = struct.unpack_from("<I", record, 0x18)[0]
+    record[used_entry_size + 16 : used_entry_size + 16 + len(residual)] = residual
+
+    entry = parse_entry(bytes(record), 671)
+
+    assert len(entry.slack) == entry.total_entry_size - entry.used_entry_size
+    assert residual in entry.slack
+    assert [a.attribute_content.name for a in entry.slack_attributes()] == ["REPOSI~1"]
+    assert all(a.attribute_content.name != "REPOSI~1" for a in entry.attributes() if a.type_code == 0x30)
+
+
+def test_timestamp_anomalies(sample_mft: Path):
+    record = bytearray(sample_mft.read_bytes()[671 * 1024 : 672 * 1024])
+    assert parse_entry(bytes(record)).timestamp_anomalies() == []
+
+    # $STANDARD_INFORMATION is the first attribute, created is the first field of its content.
+    attribute = struct.unpack_from("<H", record, 0x14)[0]
+    content = attribute + struct.unpack_from("<H", record, attribute + 0x14)[0]
+    # 2000-01-01 00:00:00 UTC as a FILETIME.
+    struct.pack_into("<Q", record, content, 125911584000000000)
+
+    anomalies = parse_entry(bytes(record)).timestamp_anomalies()
+
+    # One for each $FILE_NAME attribute (the Win32 and DOS names).
+    assert sorted((a.kind, a.field, a.file_name) for a in anomalies) == [
+        ("created-before-file-name", "created", "INTERN~1"),
+        ("created-before-file-name", "created", "Internet Explorer"),
+        ("zero-subsecond", "created", None),
+    ]
+
+    for anomaly in anomalies:
+        assert anomaly.timestamp == datetime.datetime(2000, 1, 1, tzinfo=datetime.timezone.utc)
+        if anomaly.kind == "created-before-file-name":
+            assert anomaly.reference_timestamp > anomaly.timestamp
+
+
+def test_entries_bodyfile(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft))
+    items = list(parser.entries_bodyfile())
+    lines = "".join(items).splitlines()
+
+    # Entries without timestamps (such as records that were never used) are skipped.
+    assert all(items)
+    with pytest.raises(TypeError):
+        parser.entries_bodyfile(include_empty=True)
+
+    assert all(len(line.split("|")) == 11 for line in lines)
+
+    entry_lines = [line.split("|") for line in lines if line.split("|")[2] == "671-4"]
+
+    # $STANDARD_INFORMATION, and the Win32 name (the DOS name is skipped).
+    assert len(entry_lines) == 2
+    assert entry_lines[0][1].endswith("Internet Explorer")
+    assert entry_lines[1][1].endswith("Internet Explorer ($FILE_NAME)")
+    assert entry_lines[0][3] == "d/drwxrwxrwx"
+    assert all(int(timestamp) > 0 for timestamp in entry_lines[0][7:])
+
+
+def test_entries_bodyfile_without_path(sample_mft: Path):
+    lines = "".join(PyMftParser(str(sample_mft)).entries_bodyfile()).splitlines()
+
+    # Entry 12 has no $FILE_NAME attribute, only $STANDARD_INFORMATION.
+    entry_lines = [line.split("|") for line in lines if line.split("|")[2].startswith("12-")]
+    assert len(entry_lines) == 1
+    assert entry_lines[0][1] == ""
+    assert all(int(timestamp) > 0 for timestamp in entry_lines[0][7:])
+
+    parser = PyMftParser(FailingReader(sample_mft.read_bytes(), 3606))
+    lines = "".join(line for line in parser.entries_bodyfile() if isinstance(line, str)).splitlines()
+
+    # Resolving the path of entry 671 fails, its file name is used instead.
+    entry_lines = [line.split("|") for line in lines if line.split("|")[2] == "671-4"]
+    assert [fields[1] for fields in entry_lines] == [
+        "Internet Explorer",
+        "Internet Explorer ($FILE_NAME)",
+    ]
+
+
+def test_entries_bodyfile_unset_timestamp(sample_mft: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    record = 671 * 1024
+    # The created timestamp of $STANDARD_INFORMATION, the first attribute.
+    attribute = record + struct.unpack_from("<H", mft, record + 0x14)[0]
+    content = attribute + struct.unpack_from("<H", mft, attribute + 0x14)[0]
+    mft[content : content + 8] = bytes(8)
+
+    lines = "".join(PyMftParser(io.BytesIO(bytes(mft))).entries_bodyfile()).splitlines()
+    fields = next(line.split("|") for line in lines if line.split("|")[2] == "671-4")
+
+    assert fields[10] == "0"
+    assert all(int(timestamp) > 0 for timestamp in fields[7:10])
+
+
+def test_entries_timeline(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft))
+    items = list(parser.entries_timeline())
+
+    # Entries without timestamps (such as records that were never used) are skipped.
+    assert all(items)
+    with pytest.raises(TypeError):
+        parser.entries_timeline(include_empty=True)
+
+    lines = b"".join(items).decode().splitlines()
+
+    assert lines[0] == "Timestamp,MACB,Source,FileNameNamespace,EntryId,Sequence,IsDeleted,FullPath"
+
+    rows = [line.split(",") for line in lines[1:] if line.split(",")[4] == "671"]
+
+    # $STANDARD_INFORMATION, and both the DOS and Win32 $FILE_NAME attributes.
+    assert [(row[1], row[2], row[3]) for row in rows] == [
+        (macb, source, namespace)
+        for source, namespace in [("SI", ""), ("FN", "DOS"), ("FN", "Win32")]
+        for macb in ["M...", ".A..", "..C.", "...B"]
+    ]
+    assert rows[-1][-1].endswith("Internet Explorer")
+
+
+def test_entries_timeline_without_path(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft))
+    rows = [line.split(",") for line in b"".join(parser.entries_timeline()).decode().splitlines()]
+
+    # Entry 12 has no $FILE_NAME attribute (its `path_status` is `unknown`).
+    assert [(row[1], row[2], row[7]) for row in rows if row[4] == "12"] == [
+        (macb, "SI", "") for macb in ["M...", ".A..", "..C.", "...B"]
+    ]
+
+    parser = PyMftParser(FailingReader(sample_mft.read_bytes(), 3606))
+    output = b"".join(rows for rows in parser.entries_timeline() if isinstance(rows, bytes))
+    rows = [line.split(",") for line in output.decode().splitlines()]
+
+    # Resolving the path of entry 671 fails (its `path_status` is `error`), so its rows are
+    # named after its file names.
+    assert [(row[2], row[7]) for row in rows if row[4] == "671"][::4] == [
+        ("SI", "Internet Explorer"),
+        ("FN", "INTERN~1"),
+        ("FN", "Internet Explorer"),
+    ]
+
+
+def test_to_csv(sample_mft: Path, tmp_path: Path):
+    output = tmp_path / "mft.csv"
+
+    written = PyMftParser(str(sample_mft)).to_csv(str(output))
+
+    assert written == 13068
+    assert output.read_bytes() == b"".join(PyMftParser(str(sample_mft)).entries_csv())
+
+
+def test_to_jsonl_strict_skips_invalid_records(sample_mft: Path, tmp_path: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    mft[672 * 1024 - 2 : 672 * 1024] = b"\xDE\xAD"
+    output = tmp_path / "mft.jsonl"
+
+    written = PyMftParser(io.BytesIO(bytes(mft))).to_jsonl(output, strict=True)
+
+    records = [json.loads(line) for line in output.read_text().splitlines()]
+    assert written == len(records) == 13067
+    assert all(r["header"]["record_number"] != 671 for r in records)
+
+    written = PyMftParser(io.BytesIO(bytes(mft))).to_jsonl(output)
+    assert written == 13068
+
+
+@pytest.mark.parametrize("method", ["to_csv", "to_jsonl", "to_parquet"])
+def test_invalid_output_path_keeps_parser(sample_mft: Path, tmp_path: Path, method: str):
+    parser = PyMftParser(str(sample_mft))
+
+    with pytest.raises(FileNotFoundError):
+        getattr(parser, method)(tmp_path / "missing" / "mft.out")
+
+    assert getattr(parser, method)(tmp_path / "mft.out") == 13068
+
+
+def test_csv_options(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft))
+    lines = parser.entries_csv(
+        columns=["FullPath", "EntryId", "StandardInfoCreated"],
+        delimiter=";",
+        quoting="all",
+        timestamp_format="%Y-%m-%d %H:%M:%S",
+    )
+
+    assert next(lines) == b'"FullPath";"EntryId";"StandardInfoCreated"\n"$MFT";"0";"2007-06-30 12:50:52"\n'
+    assert next(lines) == b'"$MFTMirr";"1";"2007-06-30 12:50:52"\n'
+
+
+def test_csv_without_header(sample_mft: Path, tmp_path: Path):
+    output = tmp_path / "mft.csv"
+
+    PyMftParser(str(sample_mft)).to_csv(str(output), columns=["EntryId"], header=False)
+
+    assert output.read_text().splitlines()[:2] == ["0", "1"]
+
+
+@pytest.mark.parametrize(
+    "options",
+    [
+        {"columns": ["NotAColumn"]},
+        {"delimiter": ",,"},
+        {"quoting": "sometimes"},
+        {"timestamp_format": "%"},
+    ],
+)
+def test_invalid_csv_options_raise(sample_mft: Path, options: dict):
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).entries_csv(**options)
+
+
+def test_json_flat(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft))
+    entry = json.loads(next(parser.entries_json(flat=True)))
+
+    assert entry["EntryId"] == 0
+    assert entry["FullPath"] == "$MFT"
+    assert entry["StandardInfoCreated"] == "2007-06-30T12:50:52.252395Z"
+    assert "attributes" not in entry
+
+
+def test_json_fields(sample_mft: Path, tmp_path: Path):
+    parser = PyMftParser(str(sample_mft))
+    entry = next(parser.entries_json(fields=["path_status", "full_paths"]))
+
+    assert list(json.loads(entry)) == ["path_status", "full_paths"]
+
+    output = tmp_path / "mft.jsonl"
+    PyMftParser(str(sample_mft)).to_jsonl(output, flat=True, fields=["FullPath", "EntryId"])
+
+    assert output.read_text().splitlines()[:2] == [
+        '{"FullPath":"$MFT","EntryId":0}',
+        '{"FullPath":"$MFTMirr","EntryId":1}',
+    ]
+
+
+def test_json_pretty(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft))
+    entry = next(parser.entries_json(pretty=True, flat=True, fields=["EntryId"]))
+
+    assert entry == '{\n  "EntryId": 0\n}'
+
+
+@pytest.mark.parametrize(
+    "options",
+    [
+        {"fields": ["FullPath"]},
+        {"flat": True, "fields": ["full_paths"]},
+    ],
+)
+def test_invalid_json_fields_raise(sample_mft: Path, options: dict):
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).entries_json(**options)
+
+
+def test_json_full_path(sample_mft: Path):
+    parser = PyMftParser(str(sample_mft))
+    entries = {e.entry_id: e for e in parser.entries()}
+    records = [json.loads(r) for r in PyMftParser(str(sample_mft)).entries_json()]
+
+    assert len(records) == len(entries)
+    for record in records:
+        entry = entries[record["header"]["record_number"]]
+        assert record["full_path"] == entry.full_path
+        assert [p["path"] for p in record["full_paths"]] == [p.path for p in entry.full_paths]
+
+
+def test_unknown_full_path(sample_mft: Path):
+    entries = {e.entry_id: e for e in PyMftParser(str(sample_mft)).entries()}
+    records = [json.loads(r) for r in PyMftParser(str(sample_mft)).entries_json()]
+    records = {r["header"]["record_number"]: r for r in records}
+    lines = b"".join(PyMftParser(str(sample_mft)).entries_csv()).decode().splitlines()
+    rows = {int(row["EntryId"]): row for row in csv.DictReader(lines)}
+
+    # These entries have no $FILE_NAME attribute.
+    for entry_id in [12, 13, 14]:
+        assert entries[entry_id].path_status == "unknown"
+        assert entries[entry_id].full_path is None
+        assert records[entry_id]["full_path"] is None
+        assert rows[entry_id]["FullPath"] == ""
+
+
+def test_entries_dict(sample_mft: Path):
+    dicts = PyMftParser(str(sample_mft)).entries_dict()
+    records = PyMftParser(str(sample_mft)).entries_json()
+
+    for _ in range(100):
+        assert next(dicts) == json.loads(next(records))
+
+    parser = PyMftParser(str(sample_mft))
+    entry = next(parser.entries_dict(flat=True, fields=["EntryId", "FullPath"]))
+
+    assert entry == {"EntryId": 0, "FullPath": "$MFT"}
+
+
+def test_to_parquet(sample_mft: Path, tmp_path: Path):
+    output = tmp_path / "mft.parquet"
+
+    written = PyMftParser(str(sample_mft)).to_parquet(output, batch_size=5000)
+
+    data = output.read_bytes()
+    assert written == 13068
+    assert data[:4] == data[-4:] == b"PAR1"
+
+    pq = pytest.importorskip("pyarrow.parquet")
+
+    table = pq.read_table(output)
+    expected = PyMftParser(str(sample_mft)).to_arrow().read_all()
+
+    assert table.num_rows == 13068
+    assert table.schema.equals(expected.schema)
+    assert table.column("EntryId").to_pylist() == expected.column("EntryId").to_pylist()
+
+
+def test_to_arrow(sample_mft: Path):
+    pa = pytest.importorskip("pyarrow")
+
+    reader = PyMftParser(str(sample_mft)).to_arrow(batch_size=5000)
+    table = reader.read_all()
+
+    assert table.num_rows == 13068
+    assert table.schema.field("EntryId").type == pa.uint64()
+    assert table.schema.field("StandardInfoCreated").type == pa.timestamp("us", tz="UTC")
+    assert table.column("FullPath")[0].as_py() == "$MFT"
+
+
+def test_arrow_batch_size_must_be_positive(sample_mft: Path, tmp_path: Path):
+    with pytest.raises(ValueError):
+        PyMftParser(str(sample_mft)).to_parquet(tmp_path / "mft.parquet", batch_size=0)
+
+
+def test_entries_batched(sample_mft: Path):
+    batches = list(PyMftParser(str(sample_mft)).entries_batched(batch_size=5000))
+
+    assert [len(b) for b in batches] == [5000, 5000, 3068]
+    assert [e.entry_id for b in batches for e in b] == [
+        e.entry_id for e in PyMftParser(str(sample_mft)).entries()
+    ]
+
+
+def test_parsers_in_threads(sample_mft: Path, tmp_path: Path):
+    def export(i: int) -> str:
+        output = tmp_path / f"mft{i}.jsonl"
+        PyMftParser(str(sample_mft)).to_jsonl(output)
+        return output.read_text()
+
+    def count(_: int) -> int:
+        return sum(1 for _ in PyMftParser(str(sample_mft)).entries())
+
+    with ThreadPoolExecutor(max_workers=4) as executor:
+        exports = list(executor.map(export, range(4)))
+        counts = list(executor.map(count, range(4)))
+
+    assert all(e == exports[0] for e in exports)
+    assert counts == [13068] * 4
+
+
+def test_threaded_export_matches_sequential(sample_mft: Path, tmp_path: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    mft[672 * 1024 - 2 : 672 * 1024] = b"\xDE\xAD"
+    torn_mft = tmp_path / "MFT"
+    torn_mft.write_bytes(bytes(mft))
+
+    for threads in [1, 3]:
+        assert PyMftParser(str(torn_mft)).to_csv(tmp_path / f"{threads}.csv", threads=threads, strict=True) == 13067
+        assert PyMftParser(str(torn_mft)).to_jsonl(tmp_path / f"{threads}.jsonl", threads=threads) == 13068
+
+    assert (tmp_path / "1.csv").read_bytes() == (tmp_path / "3.csv").read_bytes()
+    assert (tmp_path / "1.jsonl").read_bytes() == (tmp_path / "3.jsonl").read_bytes()
+
+
+def test_threads_need_a_path(sample_mft: Path, tmp_path: Path):
+    with pytest.raises(ValueError):
+        PyMftParser(io.BytesIO(sample_mft.read_bytes())).to_jsonl(tmp_path / "mft.jsonl", threads=2)
+
+
+@pytest.mark.parametrize("options", [{"mmap": True}, {"buffer_size": 1024 * 1024}, {"buffer_size": 1500}])
+def test_file_options(sample_mft: Path, options: dict):
+    expected = list(PyMftParser(str(sample_mft)).entries_json())
+
+    assert list(PyMftParser(str(sample_mft), **options).entries_json()) == expected
+
+
+@pytest.mark.parametrize("options", [{"mmap": True}, {"buffer_size": 0}])
+def test_invalid_file_options_raise(sample_mft: Path, options: dict):
+    with pytest.raises(ValueError):
+        PyMftParser(io.BytesIO(sample_mft.read_bytes()), **options)
+
+
+def test_path_like(sample_mft: Path):
+    expected = list(PyMftParser(str(sample_mft)).entries_json())
+
+    assert list(PyMftParser(sample_mft).entries_json()) == expected
+
+
+@pytest.mark.parametrize("to_memory", [bytes, bytearray, memoryview])
+def test_memory(sample_mft: Path, to_memory):
+    expected = list(PyMftParser(str(sample_mft)).entries_json())
+    data = to_memory(sample_mft.read_bytes())
+
+    assert list(PyMftParser(data).entries_json()) == expected
+    assert len(list(carve_entries(data))) == len(list(carve_entries(str(sample_mft))))
+
+
+def test_non_byte_buffer(sample_mft: Path):
+    expected = list(PyMftParser(str(sample_mft)).entries_json())
+    data = array.array("I", sample_mft.read_bytes())
+
+    assert list(PyMftParser(data).entries_json()) == expected
+    assert list(PyMftParser(memoryview(data).toreadonly()).entries_json()) == expected
+
+
+def test_writable_memory_is_copied(sample_mft: Path):
+    expected = list(PyMftParser(str(sample_mft)).entries_json())
+    data = bytearray(sample_mft.read_bytes())
+
+    parser = PyMftParser(data)
+    data[:] = bytes(len(data))
+
+    assert list(parser.entries_json()) == expected
+
+
+def test_threaded_export_from_memory(sample_mft: Path, tmp_path: Path):
+    PyMftParser(sample_mft).to_jsonl(tmp_path / "sequential.jsonl")
+    PyMftParser(sample_mft.read_bytes()).to_jsonl(tmp_path / "threaded.jsonl", threads=3)
+
+    assert (tmp_path / "threaded.jsonl").read_bytes() == (tmp_path / "sequential.jsonl").read_bytes()
+
+
+def test_non_contiguous_memory_raises(sample_mft: Path):
+    with pytest.raises(ValueError):
+        PyMftParser(memoryview(sample_mft.read_bytes())[::2])
+
+
+class NonSeekable(io.RawIOBase):
+    """Data which can only be read in order, like a pipe or a socket."""
+
+    def __init__(self, data: bytes):
+        self._data = io.BytesIO(data)
+
+    def readable(self) -> bool:
+        return True
+
+    def readinto(self, buffer) -> int:
+        # Short reads, like a socket.
+        data = self._data.read(min(len(buffer), 1000))
+        buffer[: len(data)] = data
+        return len(data)
+
+
+def test_stream(sample_mft: Path):
+    expected = list(PyMftParser(str(sample_mft)).entries_json())
+    stream = io.BufferedReader(NonSeekable(sample_mft.read_bytes()))
+
+    assert not stream.seekable()
+    assert list(PyMftParser(stream).entries_json()) == expected
+
+
+def test_stream_option(sample_mft: Path, tmp_path: Path):
+    expected = list(PyMftParser(str(sample_mft)).entries_json(include_empty=True))
+
+    compressed = tmp_path / "MFT.gz"
+    compressed.write_bytes(gzip.compress(sample_mft.read_bytes()))
+
+    with gzip.open(compressed) as stream:
+        assert list(PyMftParser(stream, stream=True).entries_json(include_empty=True)) == expected
+
+    assert list(PyMftParser(sample_mft, stream=True).entries_json(include_empty=True)) == expected
+
+
+def test_stream_has_no_number_of_entries(sample_mft: Path):
+    parser = PyMftParser(io.BufferedReader(NonSeekable(sample_mft.read_bytes())))
+
+    with pytest.raises(RuntimeError):
+        parser.number_of_entries()
+
+    assert len(list(parser.entries())) == 13068
+
+
+def test_stream_corrupt_parent_is_an_orphan(sample_mft: Path):
+    mft = bytearray(sample_mft.read_bytes())
+    patch_parent_reference(mft, 0, 0xFFFFFFFFFF, 1)
+    data = bytes(mft) + sample_mft.read_bytes() * 2
+
+    raw = NonSeekable(data)
+    entry = next(PyMftParser(io.BufferedReader(raw)).entries())
+
+    assert entry.entry_id == 0
+    assert entry.path_status == "orphan"
+    # The parent isn't looked for past the read-ahead window.
+    assert raw._data.tell() < len(data) // 2
+
+
+def test_stream_cannot_be_carved(sample_mft: Path):
+    with pytest.raises(ValueError):
+        carve_entries(io.BufferedReader(NonSeekable(sample_mft.read_bytes())))
+
+
+def test_invalid_stream_options_raise(sample_mft: Path, tmp_path: Path):
+    with pytest.raises(ValueError):
+        PyMftParser(sample_mft, stream=True, mmap=True)
+
+    with pytest.raises(ValueError):
+        PyMftParser(sample_mft, stream=True).to_csv(tmp_path / "mft.csv", threads=2)
+
+
+def zipped(files: dict, compression: int = zipfile.ZIP_DEFLATED) -> bytes:
+    archive = io.BytesIO()
+    with zipfile.ZipFile(archive, "w", compression) as z:
+        for name, data in files.items():
+            z.writestr(name, data)
+
+    return archive.getvalue()
+
+
+@pytest.mark.parametrize(
+    "compress",
+    [
+        gzip.compress,
+        lzma.compress,
+        lambda data: zipped({"C/$Extend/$UsnJrnl": b"", "C/$MFT": data, "C/$LogFile": b""}),
+        lambda data: zipped({"MFT": data}, zipfile.ZIP_STORED),
+    ],
+)
+def test_compressed(sample_mft: Path, tmp_path: Path, compress):
+    expected = list(PyMftParser(str(sample_mft)).entries_json())
+
+    compressed = tmp_path / "MFT.compressed"
+    compressed.write_bytes(compress(sample_mft.read_bytes()))
+
+    assert list(PyMftParser(compressed).entries_json()) == expected
+    assert list(PyMftParser(compressed.read_bytes()).entries_json()) == expected
+
+    with open(compressed, "rb") as f:
+        assert list(PyMftParser(f).entries_json()) == expected
+
+
+def test_compressed_zstd(sample_mft: Path):
+    zstandard = pytest.importorskip("zstandard")
+    expected = list(PyMftParser(str(sample_mft)).entries_json())
+
+    compressed = zstandard.ZstdCompressor().compress(sample_mft.read_bytes())
+
+    assert list(PyMftParser(compressed).entries_json()) == expected
+
+
+def test_compressed_stream(sample_mft: Path):
+    expected = list(PyMftParser(str(sample_mft)).entries_json())
+    stream = io.BufferedReader(NonSeekable(gzip.compress(sample_mft.read_bytes())))
+
+    assert list(PyMftParser(stream).entries_json()) == expected
+
+
+@pytest.mark.parametrize(
+    "files",
+    [
+        {"C/$LogFile": b"", "C/$Boot": b""},
+        {"C/$MFT": b"", "D/$MFT": b""},
+    ],
+)
+def test_zip_without_a_single_mft_raises(files: dict):
+    with pytest.raises(ValueError):
+        PyMftParser(zipped(files))
+
+
+def test_zipped_stream_raises(sample_mft: Path):
+    with pytest.raises(ValueError):
+        PyMftParser(io.BufferedReader(NonSeekable(zipped({"$MFT": sample_mft.read_bytes()}))))